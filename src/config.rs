@@ -0,0 +1,247 @@
+// A small TOML-like format for settings files:
+//
+//   # comments take up a whole line
+//   [section]
+//   number = 10
+//   flag = true
+//   text = "quoted"
+//   list = ["a", "b", 3]
+//
+// Every setting has to be inside a section.
+
+use crate::err;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Value {
+    Int(i64),
+    Bool(bool),
+    Str(String),
+    List(Vec<Value>),
+}
+
+impl Value {
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Section {
+    pub name: String,
+    // Line number of the section header, for error reporting.
+    pub line: usize,
+    pub settings: Vec<(String, Value, usize)>,
+}
+
+impl Section {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.settings
+            .iter()
+            .find(|(k, _, _)| k == key)
+            .map(|(_, v, _)| v)
+    }
+}
+
+pub fn parse(s: &str) -> Result<Vec<Section>, err::ParseError> {
+    let mut sections: Vec<Section> = Vec::new();
+
+    for (idx, line) in s.split('\n').enumerate() {
+        let line_num = idx + 1;
+        let clean_line = line.trim();
+        if clean_line.is_empty() || clean_line.starts_with('#') {
+            continue;
+        }
+
+        if clean_line.starts_with('[') {
+            if !clean_line.ends_with(']') || clean_line.len() < 3 {
+                return Err(err::ParseError::new("invalid section header", line).on_line(line_num));
+            }
+            let name = clean_line[1..clean_line.len() - 1].trim();
+            if sections.iter().any(|s| s.name == name) {
+                return Err(err::ParseError::new("duplicate section", line).on_line(line_num));
+            }
+            sections.push(Section {
+                name: name.to_owned(),
+                line: line_num,
+                settings: Vec::new(),
+            });
+            continue;
+        }
+
+        let parts: Vec<&str> = clean_line.splitn(2, '=').map(|p| p.trim()).collect();
+        if parts.len() != 2 || parts[0].is_empty() {
+            return Err(err::ParseError::new("invalid setting format", line).on_line(line_num));
+        }
+        let value = match parse_value(parts[1]) {
+            Ok(value) => value,
+            Err(_) => {
+                return Err(err::ParseError::new("invalid setting value", line).on_line(line_num))
+            }
+        };
+
+        let section = match sections.last_mut() {
+            Some(section) => section,
+            None => {
+                return Err(
+                    err::ParseError::new("setting outside a section", line).on_line(line_num)
+                )
+            }
+        };
+        if section.get(parts[0]).is_some() {
+            return Err(err::ParseError::new("duplicate setting", line).on_line(line_num));
+        }
+        section
+            .settings
+            .push((parts[0].to_owned(), value, line_num));
+    }
+
+    Ok(sections)
+}
+
+fn parse_value(s: &str) -> Result<Value, err::ParseError> {
+    let (value, rest) = parse_value_prefix(s.trim())?;
+    if !rest.trim().is_empty() {
+        return Err(err::ParseError::new("unexpected trailing input", rest));
+    }
+    Ok(value)
+}
+
+// Parses a single value off the front of s and returns whatever is left.
+fn parse_value_prefix(s: &str) -> Result<(Value, &str), err::ParseError> {
+    if let Some(quoted) = s.strip_prefix('"') {
+        return match quoted.find('"') {
+            Some(end) => Ok((Value::Str(quoted[..end].to_owned()), &quoted[end + 1..])),
+            None => Err(err::ParseError::new("unterminated string", s)),
+        };
+    }
+
+    if let Some(mut rest) = s.strip_prefix('[') {
+        let mut items = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::List(items), after));
+            }
+
+            let (item, after) = parse_value_prefix(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                return Err(err::ParseError::new("expected ',' or ']'", rest));
+            }
+        }
+    }
+
+    let end = s
+        .find(|c: char| c == ',' || c == ']' || c.is_whitespace())
+        .unwrap_or(s.len());
+    let (raw, rest) = s.split_at(end);
+    match raw {
+        "true" => Ok((Value::Bool(true), rest)),
+        "false" => Ok((Value::Bool(false), rest)),
+        _ => match raw.parse::<i64>() {
+            Ok(v) => Ok((Value::Int(v), rest)),
+            Err(_) => Err(err::ParseError::new("invalid value", raw)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sections() {
+        let sections = parse(
+            r##"
+            # a comment
+            [first]
+            number = -10
+            flag = true
+            text = "#quoted text"
+
+            [second]
+            list = ["a", 2, false, []]
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].name, "first");
+        assert_eq!(sections[0].line, 3);
+        assert_eq!(sections[0].get("number"), Some(&Value::Int(-10)));
+        assert_eq!(sections[0].get("flag"), Some(&Value::Bool(true)));
+        assert_eq!(
+            sections[0].get("text").and_then(|v| v.as_str()),
+            Some("#quoted text"),
+        );
+        assert_eq!(sections[0].get("list"), None);
+        assert_eq!(
+            sections[1].get("list"),
+            Some(&Value::List(vec![
+                Value::Str(String::from("a")),
+                Value::Int(2),
+                Value::Bool(false),
+                Value::List(vec![]),
+            ])),
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            parse("a = 1"),
+            Err(err::ParseError::new("setting outside a section", "a = 1").on_line(1)),
+        );
+        assert_eq!(
+            parse("[a]\nb = \"open"),
+            Err(err::ParseError::new("invalid setting value", "b = \"open").on_line(2)),
+        );
+        assert_eq!(
+            parse("[a]\nb = [1 2]"),
+            Err(err::ParseError::new("invalid setting value", "b = [1 2]").on_line(2)),
+        );
+        assert_eq!(
+            parse("[a]\nb = 1\nb = 2"),
+            Err(err::ParseError::new("duplicate setting", "b = 2").on_line(3)),
+        );
+        assert_eq!(
+            parse("[a]\n[a]"),
+            Err(err::ParseError::new("duplicate section", "[a]").on_line(2)),
+        );
+        assert_eq!(
+            parse("[a"),
+            Err(err::ParseError::new("invalid section header", "[a").on_line(1)),
+        );
+        assert_eq!(
+            parse("[a]\nb"),
+            Err(err::ParseError::new("invalid setting format", "b").on_line(2)),
+        );
+    }
+}
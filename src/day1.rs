@@ -1,69 +1,998 @@
 use std::fs::File;
 use std::io::prelude::*;
 
-use rayon::prelude::*;
-
 use crate::days;
+use crate::err;
 
 #[derive(Debug)]
 pub struct Day{}
-impl days::Day for Day {
-    fn run(&self) {
-        println!("running day 1");
+impl Day {
+    fn load_nums<T: Entry>(&self) -> Result<Vec<T>, err::ParseError> {
         let mut file = match File::open("data/01/input.txt") {
             Ok(file) => file,
-            Err(e) => panic!(e),
+            Err(e) => panic!("{:?}", e),
         };
         let mut contents = String::new();
-        match file.read_to_string(&mut contents) {
-            Err(e) => panic!(e),
-            _ => {},
-        };
+        if let Err(e) = file.read_to_string(&mut contents) {
+            panic!("{:?}", e)
+        }
 
-        let lines = contents.split("\n");
+        parse_entries(&contents)
+    }
 
-        let nums = lines
-            .into_iter()
-            .map(|l| l.parse::<u32>().unwrap())
-            .collect::<Vec<u32>>();
+    fn sums<T: Entry>(&self, count: usize, total: &str) {
+        let total = match total.parse::<T>() {
+            Ok(total) => total,
+            Err(_) => {
+                println!("could not parse total: {}", total);
+                return;
+            }
+        };
+        let nums = match self.load_nums::<T>() {
+            Ok(nums) => nums,
+            Err(e) => {
+                println!("could not parse entries: {}", e);
+                return;
+            }
+        };
 
-        let matches = find_sums(&nums, 3, 2020);
+        let matches = k_sums(&nums, count, total);
         println!("{:?}", matches);
         for m in matches {
-            println!("product: {}", m.into_iter().product::<u32>());
+            match product::<T, T::Wide>(&m) {
+                Ok(product) => println!("product: {}", product),
+                Err(e) => println!("{}", e),
+            }
+        }
+    }
+
+    fn subsets(&self, nums: &[u32], total: u32, limit: usize) {
+        let sums = match SubsetSums::new(nums, total) {
+            Ok(sums) => sums,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        };
+        match sums.count() {
+            Ok(count) => println!("subsets: {}", count),
+            Err(e) => println!("{}", e),
+        }
+        for subset in sums.subsets(limit) {
+            println!("{:?}", subset);
         }
     }
 }
 
-fn find_sums(nums: &Vec<u32>, count: u32, total: u32) -> Vec<Vec<u32>> {
-    fn sums(check_nums: &Vec<u32>, count: u32, total: u32, current: &Vec<u32>) -> Vec<Vec<u32>> {
-        check_nums
-            .iter()
-            .map(|chk| {
-                let next_count = count - 1;
+impl days::Day for Day {
+    fn run(&self) {
+        println!("running day 1");
+        self.sums::<u32>(3, "2020");
+    }
 
-                let mut next_current = current.clone();
-                next_current.push(*chk);
-                next_current.sort();
+    // Usage: 1 sum <count> [total] [u32|u64|u128|i32|i64|i128]
+    //        1 closest <count> <target>
+    //        1 subsets [total] [limit]
+    fn run_with_args(&self, args: &[String]) {
+        match args.first().map(|a| a.as_str()) {
+            Some("sum") => {
+                let count = match parse_arg::<usize>(args.get(1), "count") {
+                    Some(count) => count,
+                    None => return,
+                };
+                let total = args.get(2).map_or("2020", |t| t.as_str());
+                match args.get(3).map_or("u32", |t| t.as_str()) {
+                    "u32" => self.sums::<u32>(count, total),
+                    "u64" => self.sums::<u64>(count, total),
+                    "u128" => self.sums::<u128>(count, total),
+                    "i32" => self.sums::<i32>(count, total),
+                    "i64" => self.sums::<i64>(count, total),
+                    "i128" => self.sums::<i128>(count, total),
+                    ty => println!("unknown type: {}", ty),
+                }
+            }
+            Some("closest") => {
+                if let (Some(count), Some(target)) = (
+                    parse_arg::<usize>(args.get(1), "count"),
+                    parse_arg::<u32>(args.get(2), "target"),
+                ) {
+                    match self
+                        .load_nums::<u32>()
+                        .map(|nums| closest_sum(&nums, count, target))
+                    {
+                        Ok(Some(c)) => println!("{:?} sums to {}", c, c.iter().sum::<u32>()),
+                        Ok(None) => println!("fewer than {} entries", count),
+                        Err(e) => println!("could not parse entries: {}", e),
+                    }
+                }
+            }
+            Some("subsets") => {
+                let default_total = String::from("2020");
+                let default_limit = String::from("10");
+                if let (Some(total), Some(limit)) = (
+                    parse_arg::<u32>(Some(args.get(1).unwrap_or(&default_total)), "total"),
+                    parse_arg::<usize>(Some(args.get(2).unwrap_or(&default_limit)), "limit"),
+                ) {
+                    match self.load_nums::<u32>() {
+                        Ok(nums) => self.subsets(&nums, total, limit),
+                        Err(e) => println!("could not parse entries: {}", e),
+                    }
+                }
+            }
+            Some(cmd) => println!("unknown command: {}", cmd),
+            None => self.run(),
+        }
+    }
+}
+
+fn parse_arg<T: std::str::FromStr>(arg: Option<&String>, name: &str) -> Option<T> {
+    match arg.map(|a| a.parse::<T>()) {
+        Some(Ok(v)) => Some(v),
+        Some(Err(_)) => {
+            println!("could not parse {}: {}", name, arg.unwrap());
+            None
+        }
+        None => {
+            println!("missing {}", name);
+            None
+        }
+    }
+}
 
-                let next_sum = next_current.iter().sum::<u32>();
+// The integer types an expense report can be read as. Wide is what
+// products are computed in, the widest type of the same signedness.
+trait Entry: Copy + Ord + std::fmt::Debug + std::fmt::Display + std::str::FromStr {
+    type Wide: Entry + From<Self>;
 
-                if next_count > 0 {
-                    sums(check_nums, next_count, total, &next_current)
-                } else if next_sum == total {
-                    vec![next_current]
-                } else {
-                    vec![]
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_sub(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! impl_entry {
+    ($wide:ty: $($t:ty),*) => {
+        $(
+            impl Entry for $t {
+                type Wide = $wide;
+
+                const ZERO: Self = 0;
+                const ONE: Self = 1;
+
+                fn checked_add(self, other: Self) -> Option<Self> {
+                    <$t>::checked_add(self, other)
                 }
-            })
-            .flatten()
-            .collect()
+
+                fn checked_sub(self, other: Self) -> Option<Self> {
+                    <$t>::checked_sub(self, other)
+                }
+
+                fn checked_mul(self, other: Self) -> Option<Self> {
+                    <$t>::checked_mul(self, other)
+                }
+            }
+        )*
     };
+}
+
+impl_entry!(u128: u8, u16, u32, u64, u128);
+impl_entry!(i128: i8, i16, i32, i64, i128);
 
-    let mut found = nums.into_par_iter()
-        .map(|c| sums(nums, count-1, total, &vec!{*c}))
-        .flatten()
-        .collect::<Vec<Vec<u32>>>();
-    found.dedup();
+// value added to itself count times, or None if that overflows.
+fn times<T: Entry>(value: T, count: usize) -> Option<T> {
+    (0..count).try_fold(T::ZERO, |acc, _| acc.checked_add(value))
+}
+
+// Compares a + b to total without overflowing. A sum too big for T is
+// bigger than any total, and one too small is smaller.
+fn cmp_sum<T: Entry>(a: T, b: T, total: T) -> std::cmp::Ordering {
+    match a.checked_add(b) {
+        Some(sum) => sum.cmp(&total),
+        None if a > T::ZERO => std::cmp::Ordering::Greater,
+        None => std::cmp::Ordering::Less,
+    }
+}
+
+// One entry per non-blank line.
+fn parse_entries<T: Entry>(s: &str) -> Result<Vec<T>, err::ParseError> {
+    let mut nums = Vec::new();
+    for (idx, line) in s.split('\n').enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match line.parse::<T>() {
+            Ok(num) => nums.push(num),
+            Err(_) => return Err(err::ParseError::new("invalid entry", line).on_line(idx + 1)),
+        }
+    }
+
+    Ok(nums)
+}
+
+// The product of a combination, computed in P so it can be wider than the
+// entries themselves.
+fn product<T, P>(nums: &[T]) -> Result<P, err::OverflowError>
+where
+    T: Entry,
+    P: Entry + From<T>,
+{
+    nums.iter()
+        .try_fold(P::ONE, |acc, n| acc.checked_mul(P::from(*n)))
+        .ok_or_else(|| {
+            err::OverflowError::new(
+                "product",
+                &format!("{:?}", nums),
+                std::any::type_name::<P>(),
+            )
+        })
+}
+
+// Every combination of count entries from nums that adds up to total. An
+// entry is used at most once, so a value only repeats in a combination as
+// often as it repeats in nums. Each combination is sorted and only appears
+// once however many ways the entries could be picked, and the combinations
+// come out in ascending order. Entries can be negative. Combinations are
+// only missed if part of their sum doesn't fit in T.
+//
+// Entries too big to be in any combination are dropped and repeats are
+// collapsed into counts, so the work depends on the distinct values left:
+// linear for two entries after sorting, quadratic for three.
+fn k_sums<T: Entry>(nums: &[T], count: usize, total: T) -> Vec<Vec<T>> {
+    let mut sorted = nums.to_vec();
+    sorted.sort_unstable();
+
+    // Anything bigger than total less the smallest possible rest of the
+    // combination can't be in one.
+    if let Some(min) = sorted.first() {
+        let rest = times(*min, count.saturating_sub(1));
+        if let Some(max) = rest.and_then(|rest| total.checked_sub(rest)) {
+            sorted.truncate(sorted.partition_point(|n| *n <= max));
+        }
+    }
+
+    let mut values: Vec<(T, usize)> = Vec::new();
+    for n in sorted {
+        match values.last_mut() {
+            Some((value, left)) if *value == n => *left += 1,
+            _ => values.push((n, 1)),
+        }
+    }
+
+    let mut found = Vec::new();
+    let mut current = Vec::with_capacity(count);
+    k_sums_from(&mut values, 0, count, total, &mut current, &mut found);
     found
-}
\ No newline at end of file
+}
+
+// Extends current with count more values from values[start..], which hold
+// each distinct value along with how many of it are still unused.
+fn k_sums_from<T: Entry>(
+    values: &mut [(T, usize)],
+    start: usize,
+    count: usize,
+    total: T,
+    current: &mut Vec<T>,
+    found: &mut Vec<Vec<T>>,
+) {
+    match count {
+        0 => {
+            if total == T::ZERO {
+                found.push(current.clone());
+            }
+        }
+        1 => {
+            if let Ok(idx) = values[start..].binary_search_by_key(&total, |(value, _)| *value) {
+                if values[start + idx].1 > 0 {
+                    current.push(total);
+                    found.push(current.clone());
+                    current.pop();
+                }
+            }
+        }
+        2 => {
+            if start >= values.len() {
+                return;
+            }
+            let (mut lo, mut hi) = (start, values.len() - 1);
+            while lo <= hi {
+                let (low, low_left) = values[lo];
+                let (high, _) = values[hi];
+                match cmp_sum(low, high, total) {
+                    std::cmp::Ordering::Less => lo += 1,
+                    std::cmp::Ordering::Greater if hi == 0 => return,
+                    std::cmp::Ordering::Greater => hi -= 1,
+                    std::cmp::Ordering::Equal => {
+                        if low_left > 0 && (lo < hi || low_left > 1) {
+                            current.extend_from_slice(&[low, high]);
+                            found.push(current.clone());
+                            current.truncate(current.len() - 2);
+                        }
+                        if hi == 0 {
+                            return;
+                        }
+                        lo += 1;
+                        hi -= 1;
+                    }
+                }
+            }
+        }
+        _ => {
+            for idx in start..values.len() {
+                let (value, left) = values[idx];
+                // Everything after value is at least value, so once count
+                // of it is over total nothing later fits either.
+                match times(value, count) {
+                    Some(least) if least > total => break,
+                    None if value > T::ZERO => break,
+                    _ => {}
+                }
+                let rest = match total.checked_sub(value) {
+                    Some(rest) => rest,
+                    None => continue,
+                };
+                if left == 0 {
+                    continue;
+                }
+
+                values[idx].1 -= 1;
+                current.push(value);
+                k_sums_from(values, idx, count - 1, rest, current, found);
+                current.pop();
+                values[idx].1 += 1;
+            }
+        }
+    }
+}
+
+// The combination of count entries whose sum is closest to target, or
+// None if there aren't count entries. Ties go to the smaller sum. Like
+// k_sums, all but the last two entries are tried exhaustively and those
+// two are found by walking in from both ends of the sorted entries.
+fn closest_sum(nums: &[u32], count: usize, target: u32) -> Option<Vec<u32>> {
+    if count > nums.len() {
+        return None;
+    }
+
+    let mut sorted = nums.to_vec();
+    sorted.sort_unstable();
+    let mut best = Closest {
+        target: target as u64,
+        best: None,
+    };
+    best.search(&sorted, count, 0, &mut Vec::with_capacity(count));
+    best.best.map(|(_, _, c)| c)
+}
+
+// The best combination found so far by closest_sum, as its distance from
+// target, its sum and its entries.
+struct Closest {
+    target: u64,
+    best: Option<(u64, u64, Vec<u32>)>,
+}
+
+impl Closest {
+    fn consider(&mut self, current: &[u32], sum: u64) {
+        let distance = sum.abs_diff(self.target);
+        let better = match &self.best {
+            Some((d, s, _)) => (distance, sum) < (*d, *s),
+            None => true,
+        };
+        if better {
+            self.best = Some((distance, sum, current.to_vec()));
+        }
+    }
+
+    // Picks count more entries from sorted, on top of current which adds
+    // up to sum.
+    fn search(&mut self, sorted: &[u32], count: usize, sum: u64, current: &mut Vec<u32>) {
+        match count {
+            0 => self.consider(current, sum),
+            1 => {
+                // The entries either side of where the rest of target would go.
+                let rest = self.target.saturating_sub(sum);
+                let idx = sorted.partition_point(|n| (*n as u64) < rest);
+                for n in sorted[idx.saturating_sub(1)..].iter().take(2) {
+                    current.push(*n);
+                    self.consider(current, sum + *n as u64);
+                    current.pop();
+                }
+            }
+            2 => {
+                let (mut lo, mut hi) = (0, sorted.len() - 1);
+                while lo < hi {
+                    let pair = sum + sorted[lo] as u64 + sorted[hi] as u64;
+                    current.extend_from_slice(&[sorted[lo], sorted[hi]]);
+                    self.consider(current, pair);
+                    current.truncate(current.len() - 2);
+                    match pair.cmp(&self.target) {
+                        std::cmp::Ordering::Less => lo += 1,
+                        std::cmp::Ordering::Greater => hi -= 1,
+                        std::cmp::Ordering::Equal => return,
+                    }
+                }
+            }
+            _ => {
+                for idx in 0..=sorted.len() - count {
+                    if idx > 0 && sorted[idx] == sorted[idx - 1] {
+                        continue;
+                    }
+                    current.push(sorted[idx]);
+                    self.search(
+                        &sorted[idx + 1..],
+                        count - 1,
+                        sum + sorted[idx] as u64,
+                        current,
+                    );
+                    current.pop();
+                }
+            }
+        }
+    }
+}
+
+// Which subsets of the entries, of any size, add up to a total. Subsets
+// are told apart by their values like in k_sums, so a repeated entry gives
+// one subset per number of times it's used rather than one per choice of
+// entries. The empty subset counts towards a total of 0.
+//
+// The tables are filled in one distinct value at a time for every sum up to
+// total, so the work and memory grow with total as well as with the number
+// of distinct values. Tables bigger than MAX_CELLS aren't built at all.
+struct SubsetSums {
+    // Each distinct value along with how many times it's in the entries.
+    values: Vec<(u32, usize)>,
+    total: usize,
+    // reachable[i][s] is whether some subset of the first i values adds up
+    // to s.
+    reachable: Vec<Vec<bool>>,
+    // How many subsets of all the values add up to total, or None if that
+    // doesn't fit in a u128.
+    count: Option<u128>,
+}
+
+impl SubsetSums {
+    const MAX_CELLS: usize = 4_000_000;
+
+    fn new(nums: &[u32], total: u32) -> Result<SubsetSums, err::OverflowError> {
+        let total = total as usize;
+        let mut sorted: Vec<u32> = nums
+            .iter()
+            .copied()
+            .filter(|n| *n as usize <= total)
+            .collect();
+        sorted.sort_unstable();
+        let mut values: Vec<(u32, usize)> = Vec::new();
+        for n in sorted {
+            match values.last_mut() {
+                Some((value, times)) if *value == n => *times += 1,
+                _ => values.push((n, 1)),
+            }
+        }
+
+        let cells = (values.len() + 1).checked_mul(total + 1);
+        if cells.is_none_or(|cells| cells > SubsetSums::MAX_CELLS) {
+            return Err(err::OverflowError::new(
+                "table",
+                &format!("{} values by sums up to {}", values.len(), total),
+                &format!("{} cells", SubsetSums::MAX_CELLS),
+            ));
+        }
+
+        let mut reachable = vec![vec![false; total + 1]];
+        reachable[0][0] = true;
+        let mut counts: Vec<Option<u128>> = vec![Some(0); total + 1];
+        counts[0] = Some(1);
+        for (value, times) in &values {
+            let value = *value as usize;
+            let mut next_reachable = vec![false; total + 1];
+            let mut next_counts = vec![Some(0); total + 1];
+            for sum in 0..=total {
+                for used in 0..=*times {
+                    let rest = match sum.checked_sub(used * value) {
+                        Some(rest) => rest,
+                        None => break,
+                    };
+                    next_reachable[sum] |= reachable.last().unwrap()[rest];
+                    next_counts[sum] = match (next_counts[sum], counts[rest]) {
+                        (Some(a), Some(b)) => a.checked_add(b),
+                        _ => None,
+                    };
+                }
+            }
+            reachable.push(next_reachable);
+            counts = next_counts;
+        }
+
+        Ok(SubsetSums {
+            values,
+            total,
+            reachable,
+            count: counts[total],
+        })
+    }
+
+    fn count(&self) -> Result<u128, err::OverflowError> {
+        self.count.ok_or_else(|| {
+            err::OverflowError::new(
+                "count",
+                &format!("subsets summing to {}", self.total),
+                "u128",
+            )
+        })
+    }
+
+    // Up to limit of the subsets, each sorted, ordered by how many times
+    // they use the largest value, then the next largest and so on.
+    fn subsets(&self, limit: usize) -> Vec<Vec<u32>> {
+        let mut found = Vec::new();
+        let mut current = Vec::new();
+        self.subsets_from(
+            self.values.len(),
+            self.total,
+            limit,
+            &mut current,
+            &mut found,
+        );
+        found
+    }
+
+    // Extends current, which holds the values picked from values[count..]
+    // largest first, with subsets of the first count values adding up to
+    // sum.
+    fn subsets_from(
+        &self,
+        count: usize,
+        sum: usize,
+        limit: usize,
+        current: &mut Vec<u32>,
+        found: &mut Vec<Vec<u32>>,
+    ) {
+        if found.len() >= limit || !self.reachable[count][sum] {
+            return;
+        }
+        if count == 0 {
+            found.push(current.iter().rev().copied().collect());
+            return;
+        }
+
+        let (value, times) = self.values[count - 1];
+        for used in 0..=times {
+            let rest = match sum.checked_sub(used * value as usize) {
+                Some(rest) => rest,
+                None => break,
+            };
+            current.extend(std::iter::repeat_n(value, used));
+            self.subsets_from(count - 1, rest, limit, current, found);
+            current.truncate(current.len() - used);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod ksums {
+        use super::super::*;
+
+        // Tries every combination of indices, for checking k_sums against.
+        fn brute_force<T>(nums: &[T], count: usize, total: T) -> Vec<Vec<T>>
+        where
+            T: Entry + Into<i128>,
+        {
+            fn pick<T: Copy>(
+                nums: &[T],
+                count: usize,
+                current: &mut Vec<T>,
+                out: &mut Vec<Vec<T>>,
+            ) {
+                if count == 0 {
+                    out.push(current.clone());
+                    return;
+                }
+                for idx in 0..nums.len() {
+                    current.push(nums[idx]);
+                    pick(&nums[idx + 1..], count - 1, current, out);
+                    current.pop();
+                }
+            }
+
+            let mut all = Vec::new();
+            pick(nums, count, &mut Vec::new(), &mut all);
+            let mut found: Vec<Vec<T>> = all
+                .into_iter()
+                .filter(|c| c.iter().map(|n| (*n).into()).sum::<i128>() == total.into())
+                .map(|mut c| {
+                    c.sort_unstable();
+                    c
+                })
+                .collect();
+            found.sort();
+            found.dedup();
+            found
+        }
+
+        // Small xorshift generator so the inputs are the same every run.
+        fn nums(seed: u64, len: usize, max: u32) -> Vec<u32> {
+            let mut state = seed;
+            (0..len)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    (state % (max as u64 + 1)) as u32
+                })
+                .collect()
+        }
+
+        #[test]
+        fn example() {
+            let nums = vec![1721, 979, 366, 299, 675, 1456];
+            assert_eq!(k_sums(&nums, 2, 2020), vec![vec![299, 1721]]);
+            assert_eq!(k_sums(&nums, 3, 2020), vec![vec![366, 675, 979]]);
+        }
+
+        #[test]
+        fn entries_used_once() {
+            assert_eq!(k_sums(&[1010, 5], 2, 2020), Vec::<Vec<u32>>::new());
+            assert_eq!(k_sums(&[1010, 1010], 2, 2020), vec![vec![1010, 1010]]);
+            assert_eq!(k_sums(&[5, 5, 10], 3, 15), Vec::<Vec<u32>>::new());
+            assert_eq!(k_sums(&[5, 5, 5], 3, 15), vec![vec![5, 5, 5]]);
+        }
+
+        #[test]
+        fn duplicates_found_once() {
+            assert_eq!(
+                k_sums(&[1, 3, 1, 3, 2, 2, 2], 2, 4),
+                vec![vec![1, 3], vec![2, 2]]
+            );
+            assert_eq!(
+                k_sums(&[0, 0, 0, 0, 1, 2, 3], 3, 3),
+                vec![vec![0, 0, 3], vec![0, 1, 2]]
+            );
+        }
+
+        #[test]
+        fn edge_counts() {
+            assert_eq!(k_sums(&[1, 2], 0, 0), vec![Vec::<u32>::new()]);
+            assert_eq!(k_sums(&[1, 2], 0, 3), Vec::<Vec<u32>>::new());
+            assert_eq!(k_sums(&[1, 2, 2], 1, 2), vec![vec![2]]);
+            assert_eq!(k_sums(&[1, 2], 3, 3), Vec::<Vec<u32>>::new());
+            assert_eq!(k_sums(&[], 2, 0), Vec::<Vec<u32>>::new());
+            assert_eq!(k_sums(&[0, 0], 2, 0), vec![vec![0, 0]]);
+        }
+
+        #[test]
+        fn large_values() {
+            assert_eq!(
+                k_sums(&[u32::MAX, 0, u32::MAX - 1, 1], 2, u32::MAX),
+                vec![vec![0, u32::MAX], vec![1, u32::MAX - 1]]
+            );
+        }
+
+        #[test]
+        fn matches_brute_force() {
+            for seed in 1..40 {
+                let nums = nums(seed, 18, 30);
+                for count in 1..=4 {
+                    let total = 20 + seed as u32;
+                    assert_eq!(
+                        k_sums(&nums, count, total),
+                        brute_force(&nums, count, total),
+                        "{:?} count {} total {}",
+                        nums,
+                        count,
+                        total
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn negative_entries() {
+            assert_eq!(
+                k_sums(&[-5i64, 10, 2025, 3000, -980, 0], 2, 2020),
+                vec![vec![-980, 3000], vec![-5, 2025]]
+            );
+            assert_eq!(
+                k_sums(&[-1i64, -1, -2, 1, 2, 0], 3, 0),
+                vec![vec![-2, 0, 2], vec![-1, -1, 2], vec![-1, 0, 1]]
+            );
+            assert_eq!(k_sums(&[-3i32, -4, -5], 2, -9), vec![vec![-5, -4]]);
+        }
+
+        #[test]
+        fn signed_matches_brute_force() {
+            for seed in 1..40 {
+                let nums: Vec<i64> = nums(seed, 16, 40)
+                    .into_iter()
+                    .map(|n| n as i64 - 20)
+                    .collect();
+                for count in 1..=4 {
+                    let total = seed as i64 - 20;
+                    assert_eq!(
+                        k_sums(&nums, count, total),
+                        brute_force(&nums, count, total),
+                        "{:?} count {} total {}",
+                        nums,
+                        count,
+                        total
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn sums_past_type_limits() {
+            assert_eq!(
+                k_sums(&[i64::MAX, i64::MIN, 1, -1, 0], 2, -1),
+                vec![vec![i64::MIN, i64::MAX], vec![-1, 0]]
+            );
+            assert_eq!(
+                k_sums(&[i64::MAX, i64::MAX, 5], 2, 5),
+                Vec::<Vec<i64>>::new()
+            );
+            assert_eq!(
+                k_sums(&[i8::MIN, i8::MIN, i8::MAX, 1], 3, i8::MIN + 1),
+                Vec::<Vec<i8>>::new()
+            );
+            assert_eq!(
+                k_sums(&[u8::MAX, u8::MAX, 0], 3, u8::MAX),
+                Vec::<Vec<u8>>::new()
+            );
+            assert_eq!(
+                k_sums(&[u8::MAX, 0, 0], 3, u8::MAX),
+                vec![vec![0, 0, u8::MAX]]
+            );
+        }
+
+        #[test]
+        fn large_input() {
+            let nums = nums(2020, 100_000, 5000);
+            for count in 2..=3 {
+                let found = k_sums(&nums, count, 2020);
+                assert!(!found.is_empty());
+                for c in &found {
+                    assert_eq!(c.len(), count);
+                    assert_eq!(c.iter().sum::<u32>(), 2020);
+                }
+                assert!(found.windows(2).all(|w| w[0] < w[1]));
+            }
+        }
+    }
+    mod product {
+        use super::super::*;
+
+        #[test]
+        fn widens() {
+            let nums = [1_000_000u32, 3_000_000, 4_000_000];
+            assert_eq!(product::<u32, u128>(&nums), Ok(12_000_000_000_000_000_000));
+            assert_eq!(
+                product::<i64, i128>(&[-2, 3, i64::MAX]),
+                Ok(-6 * i64::MAX as i128)
+            );
+            assert_eq!(product::<u32, u128>(&[]), Ok(1));
+        }
+
+        #[test]
+        fn overflow() {
+            assert_eq!(
+                product::<u32, u32>(&[1_000_000, 3_000_000, 4_000_000]),
+                Err(err::OverflowError::new(
+                    "product",
+                    "[1000000, 3000000, 4000000]",
+                    "u32"
+                ))
+            );
+            let e = product::<u128, u128>(&[u128::MAX, 2]).unwrap_err();
+            assert_eq!(
+                e.to_string(),
+                format!("product of [{}, 2] overflows u128", u128::MAX)
+            );
+        }
+    }
+
+    mod parse {
+        use super::super::*;
+
+        #[test]
+        fn entries() {
+            assert_eq!(
+                parse_entries::<i64>("1721\n-979\n\n366\n"),
+                Ok(vec![1721, -979, 366])
+            );
+            assert_eq!(
+                parse_entries::<u32>("1721\n-979\n"),
+                Err(err::ParseError::new("invalid entry", "-979").on_line(2))
+            );
+            assert_eq!(
+                parse_entries::<u8>("255\n256"),
+                Err(err::ParseError::new("invalid entry", "256").on_line(2))
+            );
+        }
+    }
+    mod closest {
+        use super::super::*;
+
+        // Every sorted combination of count entries, picked by index.
+        fn combinations(nums: &[u32], count: usize) -> Vec<Vec<u32>> {
+            if count == 0 {
+                return vec![vec![]];
+            }
+            let mut all = Vec::new();
+            for idx in 0..nums.len() {
+                for mut rest in combinations(&nums[idx + 1..], count - 1) {
+                    rest.push(nums[idx]);
+                    rest.sort_unstable();
+                    all.push(rest);
+                }
+            }
+            all
+        }
+
+        #[test]
+        fn example() {
+            let nums = vec![1721, 979, 366, 299, 675, 1456];
+            assert_eq!(closest_sum(&nums, 2, 2020), Some(vec![299, 1721]));
+            assert_eq!(closest_sum(&nums, 2, 2000), Some(vec![299, 1721]));
+            assert_eq!(closest_sum(&nums, 3, 1000), Some(vec![299, 366, 675]));
+            assert_eq!(closest_sum(&nums, 3, 2400), Some(vec![299, 366, 1721]));
+        }
+
+        #[test]
+        fn edges() {
+            assert_eq!(closest_sum(&[1, 2], 3, 3), None);
+            assert_eq!(closest_sum(&[1, 2], 0, 3), Some(vec![]));
+            assert_eq!(closest_sum(&[10, 20, 30], 1, 0), Some(vec![10]));
+            assert_eq!(closest_sum(&[10, 20, 30], 1, 100), Some(vec![30]));
+            assert_eq!(closest_sum(&[10, 20, 30], 1, 15), Some(vec![10]));
+            assert_eq!(closest_sum(&[5, 5], 2, 100), Some(vec![5, 5]));
+            assert_eq!(
+                closest_sum(&[u32::MAX, u32::MAX], 2, 0),
+                Some(vec![u32::MAX, u32::MAX])
+            );
+        }
+
+        #[test]
+        fn matches_brute_force() {
+            let mut state = 7u64;
+            for _ in 0..60 {
+                let nums: Vec<u32> = (0..10)
+                    .map(|_| {
+                        state ^= state << 13;
+                        state ^= state >> 7;
+                        state ^= state << 17;
+                        (state % 100) as u32
+                    })
+                    .collect();
+                let target = (state % 300) as u32;
+                for count in 1..=4 {
+                    let found = closest_sum(&nums, count, target).unwrap();
+                    let key = |c: &[u32]| {
+                        let sum = c.iter().map(|n| *n as u64).sum::<u64>();
+                        (sum.abs_diff(target as u64), sum)
+                    };
+                    let best = combinations(&nums, count)
+                        .iter()
+                        .map(|c| key(c))
+                        .min()
+                        .unwrap();
+                    assert_eq!(
+                        key(&found),
+                        best,
+                        "{:?} count {} target {}",
+                        nums,
+                        count,
+                        target
+                    );
+                }
+            }
+        }
+    }
+
+    mod subsets {
+        use super::super::*;
+
+        // Every subset by index, sorted and with repeats removed.
+        fn brute_force(nums: &[u32], total: u32) -> Vec<Vec<u32>> {
+            let mut found: Vec<Vec<u32>> = (0..1u32 << nums.len())
+                .map(|mask| {
+                    let mut subset: Vec<u32> = (0..nums.len())
+                        .filter(|idx| mask & (1 << idx) != 0)
+                        .map(|idx| nums[idx])
+                        .collect();
+                    subset.sort_unstable();
+                    subset
+                })
+                .filter(|subset| subset.iter().sum::<u32>() == total)
+                .collect();
+            found.sort();
+            found.dedup();
+            found
+        }
+
+        #[test]
+        fn example() {
+            let sums = SubsetSums::new(&[1721, 979, 366, 299, 675, 1456], 2020).unwrap();
+            assert_eq!(sums.count(), Ok(2));
+            let mut subsets = sums.subsets(10);
+            subsets.sort();
+            assert_eq!(subsets, vec![vec![299, 1721], vec![366, 675, 979]]);
+        }
+
+        #[test]
+        fn repeats_and_zeros() {
+            let sums = SubsetSums::new(&[5, 5, 10, 0], 10).unwrap();
+            assert_eq!(sums.count(), Ok(4));
+            assert_eq!(
+                sums.subsets(10),
+                vec![vec![5, 5], vec![0, 5, 5], vec![10], vec![0, 10]]
+            );
+            assert_eq!(
+                SubsetSums::new(&[3, 4], 0).unwrap().subsets(10),
+                vec![Vec::<u32>::new()]
+            );
+            assert_eq!(SubsetSums::new(&[3, 4], 5).unwrap().count(), Ok(0));
+            assert_eq!(
+                SubsetSums::new(&[3, 4], 5).unwrap().subsets(10),
+                Vec::<Vec<u32>>::new()
+            );
+        }
+
+        #[test]
+        fn limit() {
+            let sums = SubsetSums::new(&[1, 2, 3, 4, 5, 6], 7).unwrap();
+            assert_eq!(sums.count(), Ok(4));
+            assert_eq!(sums.subsets(2).len(), 2);
+            assert_eq!(sums.subsets(0), Vec::<Vec<u32>>::new());
+        }
+
+        #[test]
+        fn matches_brute_force() {
+            let mut state = 11u64;
+            for _ in 0..40 {
+                let nums: Vec<u32> = (0..12)
+                    .map(|_| {
+                        state ^= state << 13;
+                        state ^= state >> 7;
+                        state ^= state << 17;
+                        (state % 20) as u32
+                    })
+                    .collect();
+                let total = (state % 60) as u32;
+                let sums = SubsetSums::new(&nums, total).unwrap();
+                let expected = brute_force(&nums, total);
+                let mut found = sums.subsets(usize::MAX);
+                found.sort();
+                assert_eq!(found, expected, "{:?} total {}", nums, total);
+                assert_eq!(sums.count(), Ok(expected.len() as u128));
+            }
+        }
+
+        #[test]
+        fn count_overflow() {
+            let nums: Vec<u32> = (1..=160).collect();
+            let sums = SubsetSums::new(&nums, 6440).unwrap();
+            assert_eq!(
+                sums.count().unwrap_err().to_string(),
+                "count of subsets summing to 6440 overflows u128"
+            );
+            assert_eq!(sums.subsets(3).len(), 3);
+        }
+
+        #[test]
+        fn table_too_big() {
+            assert_eq!(
+                SubsetSums::new(&[1, 2, 3], 4_000_000_000)
+                    .err()
+                    .map(|e| e.to_string()),
+                Some(String::from(
+                    "table of 3 values by sums up to 4000000000 overflows 4000000 cells"
+                ))
+            );
+            assert!(SubsetSums::new(&[1, 2, 3], 1_000_000).is_err());
+            assert!(SubsetSums::new(&[1, 2, 3], 999_999).is_ok());
+        }
+    }
+}
@@ -1,244 +1,737 @@
 use regex::Regex;
 use std::fs::File;
 use std::io::prelude::*;
+use std::io::BufReader;
+use std::sync::OnceLock;
+
+use rayon::prelude::*;
 
 use crate::days;
 use crate::err;
 
 #[derive(Debug)]
 pub struct Day{}
-impl days::Day for Day {
-    fn run(&self) {
-        println!("running day 2");
+impl Day {
+    fn load_records(&self) -> Vec<(usize, Record)> {
         let mut file = match File::open("data/02/input.txt") {
             Ok(file) => file,
-            Err(e) => panic!(e),
+            Err(e) => panic!("{:?}", e),
         };
         let mut contents = String::new();
-        match file.read_to_string(&mut contents) {
-            Err(e) => panic!(e),
-            _ => {}
-        };
+        if let Err(e) = file.read_to_string(&mut contents) {
+            panic!("{:?}", e)
+        }
 
-        let pws = match parse_passwords::<TobogganValidator>(&contents) {
-            Ok(pws) => pws,
+        match parse_records(&contents) {
+            Ok(records) => records,
             Err(e) => panic!("couldn't parse: {:?}", e),
-        };
+        }
+    }
 
-        let valid_count = pws
-            .iter()
-            .fold(0, |acc, pw| if pw.is_valid() { acc + 1 } else { acc });
+    fn check(&self, policy: &Policy) {
+        let records = self.load_records();
+        let valid_count = records.iter().filter(|(_, r)| policy.is_valid(r)).count();
 
         println!("valid pws: {}", valid_count);
     }
+
+    fn stream(&self, policy: &Policy, path: &str, parallel: bool) {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                println!("could not read {}: {}", path, e);
+                return;
+            }
+        };
+
+        let summary = validate_stream(BufReader::new(file), policy, parallel, |e| {
+            println!("{}", e)
+        });
+        match summary {
+            Ok(summary) => {
+                println!("valid pws: {}", summary.valid);
+                println!("malformed lines: {}", summary.malformed);
+            }
+            Err(e) => println!("could not read {}: {}", path, e),
+        }
+    }
 }
 
-trait PasswordValidator: Sized {
-    fn parse(s: &str) -> Result<Self, err::ParseError>;
+impl days::Day for Day {
+    fn run(&self) {
+        println!("running day 2");
+        let registry = Registry::builtin();
+        self.check(registry.get("toboggan").unwrap());
+    }
 
-    fn is_valid(&self, _pass: &str) -> bool {
-        true
+    // Usage: 2 policies
+    //        2 check <policy>
+    //        2 compare [policy...]
+    //        2 stream <policy> [file] [parallel]
+    fn run_with_args(&self, args: &[String]) {
+        let registry = Registry::builtin();
+        match args.first().map(|a| a.as_str()) {
+            Some("policies") => {
+                for policy in registry.policies() {
+                    println!("{:<18}  {}", policy.name, policy.description);
+                }
+            }
+            Some("check") => match args.get(1).map(|name| registry.get(name)) {
+                Some(Some(policy)) => self.check(policy),
+                Some(None) => println!("unknown policy: {}", args[1]),
+                None => println!("missing policy name"),
+            },
+            Some("compare") => {
+                let mut policies = Vec::new();
+                for name in &args[1..] {
+                    match registry.get(name) {
+                        Some(policy) => policies.push(policy),
+                        None => {
+                            println!("unknown policy: {}", name);
+                            return;
+                        }
+                    }
+                }
+                if policies.is_empty() {
+                    policies = registry.policies().iter().collect();
+                }
+
+                print!("{}", Comparison::new(&self.load_records(), &policies));
+            }
+            Some("stream") => match args.get(1).map(|name| registry.get(name)) {
+                Some(Some(policy)) => {
+                    let path = args.get(2).map_or("data/02/input.txt", |p| p.as_str());
+                    match args.get(3).map(|a| a.as_str()) {
+                        Some("parallel") => self.stream(policy, path, true),
+                        Some(arg) => println!("unknown argument: {}", arg),
+                        None => self.stream(policy, path, false),
+                    }
+                }
+                Some(None) => println!("unknown policy: {}", args[1]),
+                None => println!("missing policy name"),
+            },
+            Some(cmd) => println!("unknown command: {}", cmd),
+            None => self.run(),
+        }
     }
 }
 
-#[derive(PartialEq, Debug)]
-struct SledValidator {
-    req_sequence: String,
-    req_min: u32,
-    req_max: u32,
+// How occurrences of a sequence are counted.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+struct Counting {
+    overlapping: bool,
+    ignore_case: bool,
 }
 
-impl SledValidator {
-    fn seq_count(&self, pass: &str) -> u32 {
-        pass.matches(&self.req_sequence)
-            .collect::<Vec<&str>>()
-            .len() as u32
+impl Counting {
+    fn count(&self, pass: &str, seq: &str) -> u32 {
+        if self.ignore_case {
+            return Counting {
+                ignore_case: false,
+                ..*self
+            }
+            .count(&pass.to_lowercase(), &seq.to_lowercase());
+        }
+        if !self.overlapping || seq.is_empty() {
+            return pass.matches(seq).count() as u32;
+        }
+
+        let mut count = 0;
+        let mut rest = pass;
+        while let Some(idx) = rest.find(seq) {
+            count += 1;
+            let step = rest[idx..].chars().next().map_or(1, char::len_utf8);
+            rest = &rest[idx + step..];
+        }
+        count
     }
 }
 
-impl PasswordValidator for SledValidator {
-    fn parse(s: &str) -> Result<Self, err::ParseError> {
-        let re = match Regex::new(r"(?P<min>[0-9]+)-(?P<max>[0-9]+)\s+(?P<seq>[a-z]+)") {
-            Ok(re) => re,
-            Err(_) => return Err(err::ParseError::new("invalid regex", s)),
-        };
+impl std::fmt::Display for Counting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.overlapping {
+            true => write!(f, "overlapping")?,
+            false => write!(f, "nonoverlapping")?,
+        }
+        if self.ignore_case {
+            write!(f, " nocase")?;
+        }
+        Ok(())
+    }
+}
+
+// The `a-b seq [mode...]` part in front of every password. What a and b
+// mean is up to the policy reading it. The optional modes say how seq is
+// counted, overriding whatever the policy would do otherwise.
+#[derive(PartialEq, Debug, Clone)]
+struct Header {
+    a: u32,
+    b: u32,
+    seq: String,
+    counting: Option<Counting>,
+}
+
+impl Header {
+    fn parse(s: &str) -> Result<Header, err::ParseError> {
+        static RE: OnceLock<Regex> = OnceLock::new();
+        let re = RE.get_or_init(|| {
+            Regex::new(r"^(?P<a>[0-9]+)-(?P<b>[0-9]+)\s+(?P<seq>\S+)(?P<modes>(?:\s+\S+)*)$").unwrap()
+        });
         let caps = match re.captures(s) {
             Some(caps) => caps,
             _ => return Err(err::ParseError::new("invalid validator format", s)),
         };
 
-        Ok(SledValidator {
-            req_sequence: match caps.name("seq") {
-                Some(v) => v.as_str().to_owned(),
-                _ => return Err(err::ParseError::new("invalid validator format", s)),
-            },
-            req_min: match caps.name("min") {
-                Some(v) => match v.as_str().parse::<u32>() {
-                    Ok(v) => v,
-                    _ => return Err(err::ParseError::new("invalid min value", s)),
-                },
-                _ => return Err(err::ParseError::new("invalid validator format", s)),
+        Ok(Header {
+            a: match caps["a"].parse::<u32>() {
+                Ok(v) => v,
+                _ => return Err(err::ParseError::new("invalid min value", s)),
             },
-            req_max: match caps.name("max") {
-                Some(v) => match v.as_str().parse::<u32>() {
-                    Ok(v) => v,
-                    _ => return Err(err::ParseError::new("invalid max value", s)),
-                },
-                _ => return Err(err::ParseError::new("invalid validator format", s)),
+            b: match caps["b"].parse::<u32>() {
+                Ok(v) => v,
+                _ => return Err(err::ParseError::new("invalid max value", s)),
             },
+            seq: caps["seq"].to_owned(),
+            counting: Header::parse_counting(&caps["modes"])?,
         })
     }
+
+    fn parse_counting(s: &str) -> Result<Option<Counting>, err::ParseError> {
+        let mut counting = None;
+        for mode in s.split_whitespace() {
+            let c: &mut Counting = counting.get_or_insert_with(Counting::default);
+            match mode {
+                "overlapping" => c.overlapping = true,
+                "nonoverlapping" => c.overlapping = false,
+                "nocase" => c.ignore_case = true,
+                _ => return Err(err::ParseError::new("unknown counting mode", mode)),
+            }
+        }
+        Ok(counting)
+    }
+}
+
+impl std::fmt::Display for Header {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{} {}", self.a, self.b, self.seq)?;
+        if let Some(counting) = self.counting {
+            write!(f, " {}", counting)?;
+        }
+        Ok(())
+    }
+}
+
+trait PasswordValidator: Sized {
+    fn from_header(header: &Header) -> Self;
+
+    fn is_valid(&self, pass: &str) -> bool;
+}
+
+#[derive(PartialEq, Debug)]
+struct SledValidator {
+    req_sequence: String,
+    req_min: u32,
+    req_max: u32,
+    counting: Counting,
+}
+
+impl SledValidator {
+    // Counts with the given mode, unless the header asked for its own.
+    fn with_counting(header: &Header, counting: Counting) -> SledValidator {
+        SledValidator {
+            counting: header.counting.unwrap_or(counting),
+            ..SledValidator::from_header(header)
+        }
+    }
+
+    fn seq_count(&self, pass: &str) -> u32 {
+        self.counting.count(pass, &self.req_sequence)
+    }
+}
+
+impl PasswordValidator for SledValidator {
+    fn from_header(header: &Header) -> Self {
+        SledValidator {
+            req_sequence: header.seq.clone(),
+            req_min: header.a,
+            req_max: header.b,
+            counting: header.counting.unwrap_or_default(),
+        }
+    }
+
     fn is_valid(&self, pass: &str) -> bool {
         let count = self.seq_count(pass);
         count >= self.req_min && count <= self.req_max
     }
 }
 
+// What a position in a password counts.
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Positions {
+    Bytes,
+    Chars,
+    // A char along with any combining marks, variation selectors, skin tone
+    // modifiers and zero width joined chars that follow it. Close enough to
+    // real grapheme clusters for passwords.
+    Clusters,
+}
+
+impl Positions {
+    fn extends_cluster(c: char) -> bool {
+        matches!(c,
+            '\u{0300}'..='\u{036f}'
+            | '\u{1ab0}'..='\u{1aff}'
+            | '\u{1dc0}'..='\u{1dff}'
+            | '\u{200d}'
+            | '\u{20d0}'..='\u{20ff}'
+            | '\u{fe00}'..='\u{fe0f}'
+            | '\u{fe20}'..='\u{fe2f}'
+            | '\u{1f3fb}'..='\u{1f3ff}'
+        )
+    }
+
+    // Byte offsets where each position in s starts, followed by s.len().
+    fn boundaries(&self, s: &str) -> Vec<usize> {
+        let mut bounds: Vec<usize> = match self {
+            Positions::Bytes => (0..s.len()).collect(),
+            Positions::Chars => s.char_indices().map(|(idx, _)| idx).collect(),
+            Positions::Clusters => {
+                let mut bounds = Vec::new();
+                let mut joined = false;
+                for (idx, c) in s.char_indices() {
+                    if bounds.is_empty() || !(joined || Positions::extends_cluster(c)) {
+                        bounds.push(idx);
+                    }
+                    joined = c == '\u{200d}';
+                }
+                bounds
+            }
+        };
+        bounds.push(s.len());
+        bounds
+    }
+}
+
 #[derive(PartialEq, Debug)]
 struct TobogganValidator {
     req_sequence: String,
     position1: u32,
     position2: u32,
+    positions: Positions,
 }
 
 impl TobogganValidator {
-    fn has_seq(&self, p: &str, start_pos: u32) -> bool {
-        if start_pos == 0 { return false }
+    fn with_positions(self, positions: Positions) -> TobogganValidator {
+        TobogganValidator { positions, ..self }
+    }
+
+    // bounds are the password's boundaries and seq_len the length of the
+    // sequence, both counted in self.positions.
+    fn has_seq(&self, p: &str, bounds: &[usize], seq_len: usize, start_pos: u32) -> bool {
+        if start_pos == 0 {
+            return false;
+        }
 
-        let seq_len = self.req_sequence.len();
         let start_idx = (start_pos - 1) as usize;
-        if start_idx + seq_len> p.len() {
-            return false
+        match bounds.get(start_idx..=start_idx + seq_len) {
+            Some(range) => p.as_bytes()[range[0]..range[seq_len]] == *self.req_sequence.as_bytes(),
+            None => false,
         }
+    }
 
-        for seq_idx in 0..seq_len {
-            let idx = start_idx + seq_idx;
-            if self.req_sequence.chars().nth(seq_idx) != p.chars().nth(idx) {
-                return false
-            }
-        } 
+    fn matches(&self, pass: &str) -> u32 {
+        let bounds = self.positions.boundaries(pass);
+        let seq_len = self.positions.boundaries(&self.req_sequence).len() - 1;
 
-        return true
+        let mut matches = 0;
+        if self.has_seq(pass, &bounds, seq_len, self.position1) {
+            matches += 1;
+        }
+        if self.has_seq(pass, &bounds, seq_len, self.position2) {
+            matches += 1;
+        }
+        matches
     }
 }
 
 impl PasswordValidator for TobogganValidator {
-    fn parse(s: &str) -> Result<Self, err::ParseError> {
-        let re = match Regex::new(r"(?P<pos1>[0-9]+)-(?P<pos2>[0-9]+)\s+(?P<seq>[a-z]+)") {
-            Ok(re) => re,
-            Err(_) => return Err(err::ParseError::new("invalid regex", s)),
-        };
-        let caps = match re.captures(s) {
-            Some(caps) => caps,
-            _ => return Err(err::ParseError::new("invalid validator format", s)),
-        };
+    fn from_header(header: &Header) -> Self {
+        TobogganValidator {
+            req_sequence: header.seq.clone(),
+            position1: header.a,
+            position2: header.b,
+            positions: Positions::Chars,
+        }
+    }
 
-        Ok(TobogganValidator {
-            req_sequence: match caps.name("seq") {
-                Some(v) => v.as_str().to_owned(),
-                _ => return Err(err::ParseError::new("invalid validator format", s)),
-            },
-            position1: match caps.name("pos1") {
-                Some(v) => match v.as_str().parse::<u32>() {
-                    Ok(v) => v,
-                    _ => return Err(err::ParseError::new("invalid min value", s)),
-                },
-                _ => return Err(err::ParseError::new("invalid validator format", s)),
-            },
-            position2: match caps.name("pos2") {
-                Some(v) => match v.as_str().parse::<u32>() {
-                    Ok(v) => v,
-                    _ => return Err(err::ParseError::new("invalid max value", s)),
-                },
-                _ => return Err(err::ParseError::new("invalid validator format", s)),
-            },
+    fn is_valid(&self, pass: &str) -> bool {
+        self.matches(pass) == 1
+    }
+}
+
+// A parsed line, before any policy has looked at it.
+#[derive(PartialEq, Debug)]
+struct Record {
+    header: Header,
+    password: String,
+}
+
+impl Record {
+    fn parse(s: &str) -> Result<Record, err::ParseError> {
+        let parts: Vec<&str> = s.split(':').collect();
+        if parts.len() != 2 {
+            return Err(err::ParseError::new("invalid format", s));
+        }
+
+        Ok(Record {
+            header: Header::parse(parts[0])?,
+            password: parts[1].trim().to_owned(),
         })
     }
-    fn is_valid(&self, pass: &str) -> bool {
-        let mut matches = 0;
-        if self.has_seq(pass, self.position1) {
-            matches += 1;
+}
+
+impl std::fmt::Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.header, self.password)
+    }
+}
+
+// Every non-blank line as a record, along with its line number.
+fn parse_records(s: &str) -> Result<Vec<(usize, Record)>, err::ParseError> {
+    let mut records = Vec::new();
+    for (idx, line) in s.split('\n').enumerate() {
+        if line.trim().is_empty() {
+            continue;
         }
-        if self.has_seq(pass, self.position2) {
-            matches += 1;
+        let record = Record::parse(line).map_err(|e| e.on_line(idx + 1))?;
+        records.push((idx + 1, record));
+    }
+
+    Ok(records)
+}
+
+// Totals from streaming a password file through a policy.
+#[derive(PartialEq, Debug, Default, Clone, Copy)]
+struct StreamSummary {
+    records: usize,
+    valid: usize,
+    malformed: usize,
+}
+
+impl StreamSummary {
+    fn add(
+        &mut self,
+        result: Result<bool, err::ParseError>,
+        on_error: &mut impl FnMut(err::ParseError),
+    ) {
+        match result {
+            Ok(valid) => {
+                self.records += 1;
+                if valid {
+                    self.valid += 1;
+                }
+            }
+            Err(e) => {
+                self.malformed += 1;
+                on_error(e);
+            }
         }
+    }
+}
 
-        match matches {
-            1 => true,
-            _ => false,
+// How many lines are held at once when checking in parallel.
+const STREAM_BATCH: usize = 4096;
+
+fn check_line(
+    line: &[u8],
+    number: usize,
+    policy: &Policy,
+) -> Option<Result<bool, err::ParseError>> {
+    let line = match std::str::from_utf8(line) {
+        Ok(line) => line.trim_end_matches(['\n', '\r']),
+        Err(_) => {
+            let line = String::from_utf8_lossy(line);
+            let e = err::ParseError::new("invalid utf-8", line.trim_end());
+            return Some(Err(e.on_line(number)));
         }
+    };
+    if line.trim().is_empty() {
+        return None;
     }
+
+    Some(
+        Record::parse(line)
+            .map(|record| policy.is_valid(&record))
+            .map_err(|e| e.on_line(number)),
+    )
 }
 
-#[derive(PartialEq, Debug)]
-struct Password<V> {
-    validator: V,
-    value: String,
+// Checks every line read from r against the policy without holding the
+// whole input. Malformed lines are passed to on_error, in line order, and
+// don't stop the rest from being checked.
+fn validate_stream<R, F>(
+    mut r: R,
+    policy: &Policy,
+    parallel: bool,
+    mut on_error: F,
+) -> std::io::Result<StreamSummary>
+where
+    R: BufRead,
+    F: FnMut(err::ParseError),
+{
+    let mut summary = StreamSummary::default();
+    let mut number = 0;
+    let mut line = Vec::new();
+    let mut batch: Vec<(usize, Vec<u8>)> = Vec::new();
+    loop {
+        line.clear();
+        let eof = r.read_until(b'\n', &mut line)? == 0;
+        if !eof {
+            number += 1;
+            if !parallel {
+                if let Some(result) = check_line(&line, number, policy) {
+                    summary.add(result, &mut on_error);
+                }
+                continue;
+            }
+            batch.push((number, std::mem::take(&mut line)));
+        }
+
+        if batch.len() == STREAM_BATCH || (eof && !batch.is_empty()) {
+            let results: Vec<_> = batch
+                .par_iter()
+                .filter_map(|(number, line)| check_line(line, *number, policy))
+                .collect();
+            for result in results {
+                summary.add(result, &mut on_error);
+            }
+            batch.clear();
+        }
+        if eof {
+            return Ok(summary);
+        }
+    }
 }
 
-fn parse_passwords<V: PasswordValidator>(s: &str) -> Result<Vec<Password<V>>, err::ParseError> {
-    let lines = s.split("\n");
+type Check = Box<dyn Fn(&Header, &str) -> bool + Send + Sync>;
 
-    let mut passwords = Vec::new();
-    for line in lines {
-        if line.trim() == "" {
-            continue;
+// A named way of deciding whether a password meets its header.
+struct Policy {
+    name: String,
+    description: String,
+    check: Check,
+}
+
+impl Policy {
+    fn is_valid(&self, record: &Record) -> bool {
+        (self.check)(&record.header, &record.password)
+    }
+}
+
+// Policies looked up by name at runtime. Registering a policy under a name
+// that's already taken replaces the old one.
+struct Registry {
+    policies: Vec<Policy>,
+}
+
+impl Registry {
+    fn new() -> Registry {
+        Registry {
+            policies: Vec::new(),
         }
+    }
 
-        let pass = match Password::<V>::parse(line) {
-            Ok(pass) => pass,
-            Err(e) => return Err(e),
+    fn builtin() -> Registry {
+        let mut registry = Registry::new();
+        registry.register("sled", "seq appears between a and b times", |h, p| {
+            SledValidator::from_header(h).is_valid(p)
+        });
+        registry.register(
+            "sled-overlapping",
+            "sled, counting overlapping occurrences of seq",
+            |h, p| {
+                let counting = Counting {
+                    overlapping: true,
+                    ignore_case: false,
+                };
+                SledValidator::with_counting(h, counting).is_valid(p)
+            },
+        );
+        registry.register(
+            "sled-nocase",
+            "sled, ignoring case when counting seq",
+            |h, p| {
+                let counting = Counting {
+                    overlapping: false,
+                    ignore_case: true,
+                };
+                SledValidator::with_counting(h, counting).is_valid(p)
+            },
+        );
+        registry.register(
+            "toboggan",
+            "seq is at exactly one of positions a and b",
+            |h, p| TobogganValidator::from_header(h).is_valid(p),
+        );
+        registry.register(
+            "toboggan-bytes",
+            "toboggan, with positions counted in bytes",
+            |h, p| {
+                TobogganValidator::from_header(h)
+                    .with_positions(Positions::Bytes)
+                    .is_valid(p)
+            },
+        );
+        registry.register(
+            "toboggan-clusters",
+            "toboggan, with positions counted in grapheme clusters",
+            |h, p| {
+                TobogganValidator::from_header(h)
+                    .with_positions(Positions::Clusters)
+                    .is_valid(p)
+            },
+        );
+        registry.register(
+            "exact",
+            "seq appears exactly a times, b is ignored",
+            |h, p| SledValidator::from_header(h).seq_count(p) == h.a,
+        );
+        registry.register("none", "seq is at neither position a nor b", |h, p| {
+            TobogganValidator::from_header(h).matches(p) == 0
+        });
+        registry.register(
+            "min-length",
+            "at least a characters long, b and seq are ignored",
+            |h, p| p.chars().count() >= h.a as usize,
+        );
+        registry
+    }
+
+    fn register<F>(&mut self, name: &str, description: &str, check: F)
+    where
+        F: Fn(&Header, &str) -> bool + Send + Sync + 'static,
+    {
+        let policy = Policy {
+            name: name.to_owned(),
+            description: description.to_owned(),
+            check: Box::new(check),
         };
-        passwords.push(pass);
+        match self.policies.iter().position(|p| p.name == name) {
+            Some(idx) => self.policies[idx] = policy,
+            None => self.policies.push(policy),
+        }
     }
 
-    Ok(passwords)
+    fn get(&self, name: &str) -> Option<&Policy> {
+        self.policies.iter().find(|p| p.name == name)
+    }
+
+    fn policies(&self) -> &[Policy] {
+        &self.policies
+    }
 }
 
-impl<V: PasswordValidator> Password<V> {
-    fn parse(s: &str) -> Result<Password<V>, err::ParseError> {
-        let parts: Vec<&str> = s.split(":").collect();
-        if parts.len() != 2 {
-            return Err(err::ParseError::new("invalid format", s));
+// Where the policies being compared don't all agree on a record.
+#[derive(PartialEq, Debug)]
+struct Disagreement<'a> {
+    line: usize,
+    record: &'a Record,
+    results: Vec<bool>,
+}
+
+// The results of running several policies over the same records.
+#[derive(PartialEq, Debug)]
+struct Comparison<'a> {
+    names: Vec<&'a str>,
+    counts: Vec<usize>,
+    disagreements: Vec<Disagreement<'a>>,
+}
+
+impl<'a> Comparison<'a> {
+    fn new(records: &'a [(usize, Record)], policies: &[&'a Policy]) -> Comparison<'a> {
+        let mut counts = vec![0; policies.len()];
+        let mut disagreements = Vec::new();
+        for (line, record) in records {
+            let results: Vec<bool> = policies.iter().map(|p| p.is_valid(record)).collect();
+            for (count, _) in counts.iter_mut().zip(&results).filter(|(_, valid)| **valid) {
+                *count += 1;
+            }
+            if results.iter().any(|r| *r != results[0]) {
+                disagreements.push(Disagreement {
+                    line: *line,
+                    record,
+                    results,
+                });
+            }
         }
 
-        Ok(Password {
-            validator: match V::parse(parts[0]) {
-                Ok(v) => v,
-                Err(e) => return Err(e),
-            },
-            value: parts[1].trim().to_owned(),
-        })
+        Comparison {
+            names: policies.iter().map(|p| p.name.as_str()).collect(),
+            counts,
+            disagreements,
+        }
     }
+}
 
-    fn is_valid(&self) -> bool {
-        self.validator.is_valid(&self.value)
+impl<'a> std::fmt::Display for Comparison<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, count) in self.names.iter().zip(&self.counts) {
+            writeln!(f, "{}: {} valid", name, count)?;
+        }
+
+        writeln!(f, "disagreements: {}", self.disagreements.len())?;
+        for d in &self.disagreements {
+            let results: Vec<String> = self
+                .names
+                .iter()
+                .zip(&d.results)
+                .map(|(name, valid)| {
+                    format!("{}={}", name, if *valid { "valid" } else { "invalid" })
+                })
+                .collect();
+            writeln!(f, "{}: {}  {}", d.line, d.record, results.join(" "))?;
+        }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    mod sledpass {
+    macro_rules! policy_tests {
+        ($($name:ident: $policy:expr, $line:expr, $expected:expr,)*) => {
+            $(
+                #[test]
+                fn $name() {
+                    let registry = Registry::builtin();
+                    let record = Record::parse($line).unwrap();
+                    assert_eq!(registry.get($policy).unwrap().is_valid(&record), $expected);
+                }
+            )*
+        };
+    }
+
+    mod record {
         use super::super::*;
 
         #[test]
         fn parse_valid() {
             assert_eq!(
-                Password::<SledValidator>::parse("1-10 a: laskfdjlkasjd"),
-                Ok(Password::<SledValidator> {
-                    validator: SledValidator {
-                        req_sequence: String::from("a"),
-                        req_min: 1,
-                        req_max: 10,
+                Record::parse("1-10 a: laskfdjlkasjd"),
+                Ok(Record {
+                    header: Header {
+                        a: 1,
+                        b: 10,
+                        seq: String::from("a"),
+                        counting: None,
                     },
-                    value: String::from("laskfdjlkasjd"),
+                    password: String::from("laskfdjlkasjd"),
                 })
             )
         }
         #[test]
         fn parse_no_min() {
             assert_eq!(
-                Password::<SledValidator>::parse("-10 a: laskfdjlkasjd"),
+                Record::parse("-10 a: laskfdjlkasjd"),
                 Err(err::ParseError::new("invalid validator format", "-10 a")),
             )
         }
@@ -246,7 +739,7 @@ mod tests {
         #[test]
         fn parse_no_max() {
             assert_eq!(
-                Password::<SledValidator>::parse("1- a: laskfdjlkasjd"),
+                Record::parse("1- a: laskfdjlkasjd"),
                 Err(err::ParseError::new("invalid validator format", "1- a")),
             )
         }
@@ -254,95 +747,337 @@ mod tests {
         #[test]
         fn parse_no_seq() {
             assert_eq!(
-                Password::<SledValidator>::parse("1-10: laskfdjlkasjd"),
+                Record::parse("1-10: laskfdjlkasjd"),
                 Err(err::ParseError::new("invalid validator format", "1-10")),
             );
             assert_eq!(
-                Password::<SledValidator>::parse("1-10    : laskfdjlkasjd"),
+                Record::parse("1-10    : laskfdjlkasjd"),
                 Err(err::ParseError::new("invalid validator format", "1-10    ")),
             );
         }
 
         #[test]
-        fn is_valid_valid() {
-            let p = Password::<SledValidator>::parse("1-10 a: aabbcc").unwrap();
-            assert_eq!(p.is_valid(), true);
+        fn parse_no_password() {
+            assert_eq!(
+                Record::parse("1-10 a laskfdjlkasjd"),
+                Err(err::ParseError::new(
+                    "invalid format",
+                    "1-10 a laskfdjlkasjd"
+                )),
+            );
         }
 
         #[test]
-        fn is_valid_too_few() {
-            let p = Password::<SledValidator>::parse("1-10 a: zzbbcc").unwrap();
-            assert_eq!(p.is_valid(), false);
+        fn parse_records_lines() {
+            assert_eq!(
+                parse_records("1-3 a: abcde\n\n1-3 b: cdefg\n")
+                    .unwrap()
+                    .iter()
+                    .map(|(line, _)| *line)
+                    .collect::<Vec<usize>>(),
+                vec![1, 3]
+            );
+            assert_eq!(
+                parse_records("1-3 a: abcde\n1-3 b cdefg\n"),
+                Err(err::ParseError::new("invalid format", "1-3 b cdefg").on_line(2)),
+            );
         }
+    }
+
+    mod sledpass {
+        use super::super::*;
 
         #[test]
-        fn is_valid_too_many() {
-            let p = Password::<SledValidator>::parse("1-3 a: aaaabbcc").unwrap();
-            assert_eq!(p.is_valid(), false);
+        fn from_header() {
+            assert_eq!(
+                SledValidator::from_header(&Header::parse("1-10 a").unwrap()),
+                SledValidator {
+                    req_sequence: String::from("a"),
+                    req_min: 1,
+                    req_max: 10,
+                    counting: Counting::default(),
+                }
+            )
+        }
+
+        policy_tests! {
+            is_valid_valid:    "sled", "1-10 a: aabbcc",   true,
+            is_valid_too_few:  "sled", "1-10 a: zzbbcc",   false,
+            is_valid_too_many: "sled", "1-3 a: aaaabbcc",  false,
+
+            multi_nonoverlapping:   "sled",             "2-3 aa: aaa",                 false,
+            multi_overlapping:      "sled-overlapping", "2-3 aa: aaa",                 true,
+            multi_nocase_policy:    "sled-nocase",      "2-2 ab: abAB",                true,
+            multi_case_sensitive:   "sled",             "2-2 ab: abAB",                false,
+            header_overlapping:     "sled",             "2-3 aa overlapping: aaa",     true,
+            header_nonoverlapping:  "sled-overlapping", "2-3 aa nonoverlapping: aaa",  false,
+            header_nocase:          "sled",             "3-3 a nocase: aAa",           true,
+            header_both:            "sled",             "3-3 aa overlapping nocase: aAaA", true,
+            exact_overlapping:      "exact",            "2-0 aa overlapping: aaa",     true,
+            overlapping_multibyte:  "sled-overlapping", "2-2 éé: ééé",                 true,
+        }
+
+        #[test]
+        fn count() {
+            let modes = [
+                (false, false, 1),
+                (true, false, 2),
+                (false, true, 2),
+                (true, true, 4),
+            ];
+            for (overlapping, ignore_case, expected) in modes {
+                let counting = Counting {
+                    overlapping,
+                    ignore_case,
+                };
+                assert_eq!(counting.count("aaaAA", "aa"), expected, "{}", counting);
+            }
+        }
+
+        #[test]
+        fn parse_counting() {
+            let header = Header::parse("1-3 aa nocase overlapping").unwrap();
+            assert_eq!(
+                header.counting,
+                Some(Counting {
+                    overlapping: true,
+                    ignore_case: true,
+                })
+            );
+            assert_eq!(header.to_string(), "1-3 aa overlapping nocase");
+            assert_eq!(
+                Header::parse("1-3 aa sideways"),
+                Err(err::ParseError::new("unknown counting mode", "sideways")),
+            );
+        }
+
+        #[test]
+        fn parse_whole_header() {
+            assert_eq!(
+                Header::parse("x1-3 a"),
+                Err(err::ParseError::new("invalid validator format", "x1-3 a")),
+            );
+            assert_eq!(
+                Record::parse("1-3 a b: abc"),
+                Err(err::ParseError::new("unknown counting mode", "b")),
+            );
         }
     }
-    
+
     mod tobogganpass {
         use super::super::*;
 
         #[test]
-        fn parse_valid() {
+        fn from_header() {
             assert_eq!(
-                Password::<TobogganValidator>::parse("1-10 a: laskfdjlkasjd"),
-                Ok(Password::<TobogganValidator> {
-                    validator: TobogganValidator {
-                        req_sequence: String::from("a"),
-                        position1: 1,
-                        position2: 10,
-                    },
-                    value: String::from("laskfdjlkasjd"),
-                })
+                TobogganValidator::from_header(&Header::parse("1-10 a").unwrap()),
+                TobogganValidator {
+                    req_sequence: String::from("a"),
+                    position1: 1,
+                    position2: 10,
+                    positions: Positions::Chars,
+                }
             )
         }
+
+        policy_tests! {
+            is_valid_first_valid:       "toboggan", "3-10 a: zbabzc", true,
+            is_valid_no_match:          "toboggan", "1-10 a: zbabzc", false,
+            is_valid_too_many_matches:  "toboggan", "1-5 a: ababaz",  false,
+            is_valid_past_end:          "toboggan", "3-7 a: zbabzc",  true,
+            is_valid_zero_position:     "toboggan", "0-3 a: zbabzc",  true,
+
+            chars_accented:         "toboggan",          "1-2 a: éa",             true,
+            bytes_accented:         "toboggan-bytes",    "1-2 a: éa",             false,
+            bytes_accented_shifted: "toboggan-bytes",    "1-3 a: éa",             true,
+            clusters_accented:      "toboggan-clusters", "1-2 a: éa",             true,
+            chars_combining:        "toboggan",          "1-2 a: e\u{301}a",      false,
+            clusters_combining:     "toboggan-clusters", "1-2 a: e\u{301}a",      true,
+            chars_zwj:              "toboggan",          "2-9 x: 👩\u{200d}💻x",  false,
+            clusters_zwj:           "toboggan-clusters", "2-9 x: 👩\u{200d}💻x",  true,
+            chars_wide_seq:         "toboggan",          "2-3 é: aéb",            true,
+            bytes_wide_seq:         "toboggan-bytes",    "2-3 é: aéb",            true,
+            bytes_wide_seq_split:   "toboggan-bytes",    "3-4 é: aéb",            false,
+            chars_many_multibyte:   "toboggan",          "4-5 a: ééééa",          true,
+            chars_cjk_seq:          "toboggan",          "1-3 日本: 日本語日本",   true,
+            chars_cjk_both:         "toboggan",          "1-4 日本: 日本語日本",   false,
+        }
+
         #[test]
-        fn parse_no_min() {
+        fn boundaries() {
+            assert_eq!(Positions::Bytes.boundaries("aé"), vec![0, 1, 2, 3]);
+            assert_eq!(Positions::Chars.boundaries("aé"), vec![0, 1, 3]);
+            assert_eq!(Positions::Chars.boundaries("e\u{301}"), vec![0, 1, 3]);
+            assert_eq!(Positions::Clusters.boundaries("e\u{301}x"), vec![0, 3, 4]);
             assert_eq!(
-                Password::<TobogganValidator>::parse("-10 a: laskfdjlkasjd"),
-                Err(err::ParseError::new("invalid validator format", "-10 a")),
-            )
+                Positions::Clusters.boundaries("👍\u{1f3fd}!"),
+                vec![0, 8, 9]
+            );
+            assert_eq!(Positions::Clusters.boundaries(""), vec![0]);
+        }
+    }
+
+    mod registry {
+        use super::super::*;
+
+        policy_tests! {
+            exact_matches:        "exact",      "2-9 a: abca",     true,
+            exact_too_many:       "exact",      "2-9 a: abcaa",    false,
+            none_matches:         "none",       "1-3 a: babab",    true,
+            none_first_position:  "none",       "1-3 a: abbbb",    false,
+            none_both_positions:  "none",       "1-3 a: ababa",    false,
+            min_length_long:      "min-length", "5-0 z: abcde",    true,
+            min_length_short:     "min-length", "5-0 z: abcd",     false,
         }
 
         #[test]
-        fn parse_no_max() {
+        fn lookup() {
+            let registry = Registry::builtin();
+            let names: Vec<&str> = registry
+                .policies()
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect();
             assert_eq!(
-                Password::<TobogganValidator>::parse("1- a: laskfdjlkasjd"),
-                Err(err::ParseError::new("invalid validator format", "1- a")),
-            )
+                names,
+                vec![
+                    "sled",
+                    "sled-overlapping",
+                    "sled-nocase",
+                    "toboggan",
+                    "toboggan-bytes",
+                    "toboggan-clusters",
+                    "exact",
+                    "none",
+                    "min-length"
+                ]
+            );
+            assert!(registry.get("sled").is_some());
+            assert!(registry.get("unknown").is_none());
         }
 
         #[test]
-        fn parse_no_seq() {
+        fn register() {
+            let mut registry = Registry::new();
+            registry.register("ends-with", "password ends with seq", |h, p| {
+                p.ends_with(&h.seq)
+            });
+            let record = Record::parse("1-2 ab: ccab").unwrap();
+            assert!(registry.get("ends-with").unwrap().is_valid(&record));
+
+            registry.register("ends-with", "replaced", |_, _| false);
+            assert_eq!(registry.policies().len(), 1);
+            assert!(!registry.get("ends-with").unwrap().is_valid(&record));
+        }
+    }
+
+    mod compare {
+        use super::super::*;
+
+        #[test]
+        fn counts_and_disagreements() {
+            let registry = Registry::builtin();
+            let records =
+                parse_records("1-3 a: abcde\n1-3 b: cdefg\n\n2-9 c: ccccccccc\n").unwrap();
+            let policies = vec![
+                registry.get("sled").unwrap(),
+                registry.get("toboggan").unwrap(),
+            ];
+            let comparison = Comparison::new(&records, &policies);
+
+            assert_eq!(comparison.counts, vec![2, 1]);
             assert_eq!(
-                Password::<TobogganValidator>::parse("1-10: laskfdjlkasjd"),
-                Err(err::ParseError::new("invalid validator format", "1-10")),
+                comparison.disagreements,
+                vec![Disagreement {
+                    line: 4,
+                    record: &records[2].1,
+                    results: vec![true, false],
+                }],
             );
             assert_eq!(
-                Password::<TobogganValidator>::parse("1-10    : laskfdjlkasjd"),
-                Err(err::ParseError::new("invalid validator format", "1-10    ")),
+                comparison.to_string(),
+                "sled: 2 valid
+toboggan: 1 valid
+disagreements: 1
+4: 2-9 c: ccccccccc  sled=valid toboggan=invalid
+",
             );
         }
 
         #[test]
-        fn is_valid_first_valid() {
-            let p = Password::<TobogganValidator>::parse("3-10 a: zbabzc").unwrap();
-            assert_eq!(p.is_valid(), true);
+        fn no_policies() {
+            let records = parse_records("1-3 a: abcde\n").unwrap();
+            let comparison = Comparison::new(&records, &[]);
+            assert_eq!(comparison.to_string(), "disagreements: 0\n");
+        }
+    }
+
+    mod stream {
+        use super::super::*;
+
+        fn run(input: &[u8], parallel: bool) -> (StreamSummary, Vec<String>) {
+            let registry = Registry::builtin();
+            let mut errors = Vec::new();
+            let summary = validate_stream(input, registry.get("sled").unwrap(), parallel, |e| {
+                errors.push(e.to_string())
+            })
+            .unwrap();
+            (summary, errors)
         }
 
         #[test]
-        fn is_valid_no_match() {
-            let p = Password::<TobogganValidator>::parse("1-10 a: zbabzc").unwrap();
-            assert_eq!(p.is_valid(), false);
+        fn keeps_going_past_bad_lines() {
+            let input = b"1-3 a: abcde\nnonsense\n1-3 b: cdefg\n\n2-9 c: ccccccccc\r\n1-x a: a\n";
+            for parallel in [false, true] {
+                assert_eq!(
+                    run(input, parallel),
+                    (
+                        StreamSummary {
+                            records: 3,
+                            valid: 2,
+                            malformed: 2,
+                        },
+                        vec![
+                            String::from("2:1: invalid format: nonsense"),
+                            String::from("6:1: invalid validator format: 1-x a"),
+                        ]
+                    )
+                );
+            }
+        }
+
+        #[test]
+        fn invalid_utf8() {
+            let (summary, errors) = run(b"1-3 a: a\xffa\n1-3 a: abc", false);
+            assert_eq!(summary.records, 1);
+            assert_eq!(summary.malformed, 1);
+            assert_eq!(
+                errors,
+                vec![String::from("1:1: invalid utf-8: 1-3 a: a\u{fffd}a")]
+            );
+        }
+
+        #[test]
+        fn parallel_matches_serial() {
+            let mut input = String::new();
+            for i in 0..(STREAM_BATCH * 2 + 17) {
+                match i % 101 {
+                    0 => input.push_str("bad line\n"),
+                    n => input.push_str(&format!("1-{} a: {}\n", n % 4 + 1, "a".repeat(n % 7))),
+                }
+            }
+
+            let (serial, serial_errors) = run(input.as_bytes(), false);
+            let (parallel, parallel_errors) = run(input.as_bytes(), true);
+            assert_eq!(serial, parallel);
+            assert_eq!(serial_errors, parallel_errors);
+            assert_eq!(serial.malformed, 82);
+            assert_eq!(serial.records + serial.malformed, STREAM_BATCH * 2 + 17);
         }
 
         #[test]
-        fn is_valid_too_many_matches() {
-            let p = Password::<TobogganValidator>::parse("1-5 a: ababaz").unwrap();
-            assert_eq!(p.is_valid(), false);
+        fn empty() {
+            assert_eq!(run(b"", true), (StreamSummary::default(), vec![]));
         }
     }
 }
@@ -1,60 +1,232 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
 use std::fs::File;
 use std::io::prelude::*;
+use std::ops::Range;
+
+use rayon::prelude::*;
 
 use crate::days;
 use crate::err;
+use crate::grid::Grid;
 
 #[derive(Debug)]
 pub struct Day{}
-impl days::Day for Day {
-    fn run(&self) {
-        println!("running day 3");
-        let mut file = match File::open("data/03/input.txt") {
+impl Day {
+    fn read_file(&self, path: &str) -> String {
+        let mut file = match File::open(path) {
             Ok(file) => file,
-            Err(e) => panic!(e),
+            Err(e) => panic!("{:?}", e),
         };
         let mut contents = String::new();
-        match file.read_to_string(&mut contents) {
-            Err(e) => panic!(e),
-            _ => {}
-        };
+        if let Err(e) = file.read_to_string(&mut contents) {
+            panic!("{:?}", e)
+        }
+        contents
+    }
 
-        let map = match Map::parse(&contents) {
+    fn load_map(&self) -> Map {
+        match Map::parse(&self.read_file("data/03/input.txt")) {
             Ok(map) => map,
-            Err(e) => panic!(e),
-        };
+            Err(e) => panic!("{:?}", e),
+        }
+    }
+}
 
-        let tree_counts = vec![
-            map.encounters(
-                Movement::with_moves(vec![Move::Right(1), Move::Down(1)]),
-                MapElement::Tree,
-            ),
-            map.encounters(
-                Movement::with_moves(vec![Move::Right(3), Move::Down(1)]),
-                MapElement::Tree,
-            ),
-            map.encounters(
-                Movement::with_moves(vec![Move::Right(5), Move::Down(1)]),
-                MapElement::Tree,
-            ),
-            map.encounters(
-                Movement::with_moves(vec![Move::Right(7), Move::Down(1)]),
-                MapElement::Tree,
-            ),
-            map.encounters(
-                Movement::with_moves(vec![Move::Right(1), Move::Down(2)]),
-                MapElement::Tree,
-            ),
+impl days::Day for Day {
+    fn run(&self) {
+        println!("running day 3");
+        let map = self.load_map();
+
+        let slopes = [
+            Slope::new(1, 1),
+            Slope::new(3, 1),
+            Slope::new(5, 1),
+            Slope::new(7, 1),
+            Slope::new(1, 2),
         ];
+        match map.slope_product(&slopes) {
+            Ok(product) => println!("tree product: {}", product),
+            Err(e) => println!("{}", e),
+        }
+    }
 
-        println!(
-            "tree product: {}",
-            tree_counts.iter().fold(1, |acc, c| acc * c)
-        );
+    // Usage:
+    //   3 search [max_right max_down]
+    //   3 product right,down [right,down ...]
+    //   3 render right,down [text|ansi|svg|ppm] [wrapx|wrapboth|clamp|stop]
+    //   3 summary right,down [map_file [legend_file]]
+    //   3 path [bfs|dijkstra|astar] [move,move,...] [wrap|nowrap]
+    fn run_with_args(&self, args: &[String]) {
+        match args.first().map(|a| a.as_str()) {
+            Some("search") => {
+                let map = self.load_map();
+                let (max_right, max_down) = match &args[1..] {
+                    [] => (map.width() as u32, map.height() as u32),
+                    [raw_right, raw_down] => match (raw_right.parse(), raw_down.parse()) {
+                        (Ok(max_right), Ok(max_down)) => (max_right, max_down),
+                        _ => {
+                            println!("invalid search range: {} {}", raw_right, raw_down);
+                            return;
+                        }
+                    },
+                    _ => {
+                        println!("usage: 3 search [max_right max_down]");
+                        return;
+                    }
+                };
+
+                let report = map.search_slopes(0..max_right, 1..max_down);
+                for (slope, trees) in report.by_slope() {
+                    println!("{}: {} trees", slope, trees);
+                }
+                println!("slopes checked: {}", report.counts.len());
+                match report.fewest_trees() {
+                    Some((slope, trees)) => println!("fewest trees: {} on {}", trees, slope),
+                    None => println!("no slopes in range"),
+                }
+                if let Some((slope, trees)) = report.most_trees() {
+                    println!("most trees: {} on {}", trees, slope);
+                }
+            }
+            Some("product") => {
+                let mut slopes = Vec::new();
+                for raw_slope in &args[1..] {
+                    match Slope::parse(raw_slope) {
+                        Ok(slope) => slopes.push(slope),
+                        Err(e) => {
+                            println!("could not parse slope: {}", e);
+                            return;
+                        }
+                    }
+                }
+
+                let map = self.load_map();
+                for slope in &slopes {
+                    println!("{}: {} trees", slope, map.slope_trees(slope));
+                }
+                match map.slope_product(&slopes) {
+                    Ok(product) => println!("tree product: {}", product),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            Some("render") => {
+                let slope = match args.get(1).map(|s| Slope::parse(s)) {
+                    Some(Ok(slope)) => slope,
+                    Some(Err(e)) => {
+                        println!("could not parse slope: {}", e);
+                        return;
+                    }
+                    None => Slope::new(3, 1),
+                };
+                let format = match args.get(2).map(|s| RenderFormat::parse(s)) {
+                    Some(Ok(format)) => format,
+                    Some(Err(e)) => {
+                        println!("could not parse format: {}", e);
+                        return;
+                    }
+                    None => RenderFormat::Text,
+                };
+                let edges = match args.get(3).map(|s| EdgeMode::parse(s)) {
+                    Some(Ok(edges)) => edges,
+                    Some(Err(e)) => {
+                        println!("could not parse edge mode: {}", e);
+                        return;
+                    }
+                    None => EdgeMode::WrapX,
+                };
+
+                let map = self.load_map();
+                let render = map.render_path(slope.movement(), edges);
+                print!("{}", render.render(format));
+            }
+            Some("summary") => {
+                let slope = match args.get(1).map(|s| Slope::parse(s)) {
+                    Some(Ok(slope)) => slope,
+                    Some(Err(e)) => {
+                        println!("could not parse slope: {}", e);
+                        return;
+                    }
+                    None => Slope::new(3, 1),
+                };
+                let legend = match args.get(3) {
+                    Some(path) => match Legend::parse(&self.read_file(path)) {
+                        Ok(legend) => legend,
+                        Err(e) => {
+                            println!("could not parse legend: {}", e);
+                            return;
+                        }
+                    },
+                    None => Legend::default(),
+                };
+                let map_file = match args.get(2) {
+                    Some(path) => path.as_str(),
+                    None => "data/03/input.txt",
+                };
+                let map = match Map::parse_with_legend(&self.read_file(map_file), legend) {
+                    Ok(map) => map,
+                    Err(e) => {
+                        println!("could not parse map: {}", e);
+                        return;
+                    }
+                };
+
+                let summary = map.summarize(slope.movement());
+                for (name, count) in &summary.counts {
+                    println!("{}: {}", name, count);
+                }
+                println!("cost: {}", summary.cost);
+            }
+            Some("path") => {
+                let search = match args.get(1).map(|s| PathSearch::parse(s)) {
+                    Some(Ok(search)) => search,
+                    Some(Err(e)) => {
+                        println!("could not parse search: {}", e);
+                        return;
+                    }
+                    None => PathSearch::Dijkstra,
+                };
+                let mut moves = Vec::new();
+                for raw_move in args.get(2).map_or("d1,l1,r1", |s| s.as_str()).split(',') {
+                    match Move::parse(raw_move) {
+                        Ok(mov) => moves.push(mov),
+                        Err(e) => {
+                            println!("could not parse move: {}", e);
+                            return;
+                        }
+                    }
+                }
+                let wrap = match args.get(3).map(|s| s.as_str()) {
+                    Some("nowrap") => false,
+                    Some("wrap") | None => true,
+                    Some(other) => {
+                        println!("unknown wrap option: {}", other);
+                        return;
+                    }
+                };
+
+                let map = self.load_map();
+                match map.find_path(&moves, wrap, search) {
+                    Some(found) => {
+                        println!("trees: {}", found.trees);
+                        println!("moves: {}", found.path.len() - 1);
+                        let path: Vec<String> = found
+                            .path
+                            .iter()
+                            .map(|(x, y)| format!("({},{})", x, y))
+                            .collect();
+                        println!("path: {}", path.join(" "));
+                    }
+                    None => println!("no path to the bottom"),
+                }
+            }
+            Some(cmd) => println!("unknown command: {}", cmd),
+            None => self.run(),
+        }
     }
 }
 
-#[allow(dead_code)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 enum Move {
     Up(u32),
     Right(u32),
@@ -62,13 +234,43 @@ enum Move {
     Left(u32),
 }
 
+impl Move {
+    // Parses a direction letter followed by a distance, like "r3" or "D1".
+    fn parse(s: &str) -> Result<Move, err::ParseError> {
+        let clean_s = s.trim().to_lowercase();
+        let mut chars = clean_s.chars();
+        let dir = chars.next();
+        let delta = match chars.as_str().parse::<u32>() {
+            Ok(delta) => delta,
+            Err(_) => return Err(err::ParseError::new("invalid move distance", s)),
+        };
+
+        match dir {
+            Some('u') => Ok(Move::Up(delta)),
+            Some('r') => Ok(Move::Right(delta)),
+            Some('d') => Ok(Move::Down(delta)),
+            Some('l') => Ok(Move::Left(delta)),
+            _ => Err(err::ParseError::new("invalid move direction", s)),
+        }
+    }
+
+    fn delta(&self) -> (i64, i64) {
+        match *self {
+            Move::Up(d) => (0, -(d as i64)),
+            Move::Right(d) => (d as i64, 0),
+            Move::Down(d) => (0, d as i64),
+            Move::Left(d) => (-(d as i64), 0),
+        }
+    }
+}
+
 struct Movement {
     moves: Vec<Move>,
 }
 
 impl Movement {
     fn with_moves(moves: Vec<Move>) -> Movement {
-        Movement { moves: moves }
+        Movement { moves }
     }
 
     fn moves(&self) -> &Vec<Move> {
@@ -76,22 +278,164 @@ impl Movement {
     }
 }
 
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+struct Slope {
+    right: u32,
+    down: u32,
+}
+
+impl Slope {
+    fn new(right: u32, down: u32) -> Slope {
+        Slope { right, down }
+    }
+
+    // Parses slopes written as "right,down", like "3,1".
+    fn parse(s: &str) -> Result<Slope, err::ParseError> {
+        let parts: Vec<&str> = s.split(',').map(|p| p.trim()).collect();
+        if parts.len() != 2 {
+            return Err(err::ParseError::new("invalid slope format", s));
+        }
+
+        // Without moving down the traversal would never reach the bottom.
+        match (parts[0].parse(), parts[1].parse()) {
+            (Ok(_), Ok(0)) => Err(err::ParseError::new("slope has to move down", s)),
+            (Ok(right), Ok(down)) => Ok(Slope::new(right, down)),
+            _ => Err(err::ParseError::new("invalid slope value", s)),
+        }
+    }
+
+    fn movement(&self) -> Movement {
+        Movement::with_moves(vec![Move::Right(self.right), Move::Down(self.down)])
+    }
+}
+
+impl std::fmt::Display for Slope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "right {}, down {}", self.right, self.down)
+    }
+}
+
+#[derive(PartialEq, Debug)]
+struct SlopeReport {
+    counts: Vec<(Slope, u32)>,
+}
+
+impl SlopeReport {
+    // Every count, ordered by right then down.
+    fn by_slope(&self) -> Vec<(Slope, u32)> {
+        let mut counts = self.counts.clone();
+        counts.sort();
+        counts
+    }
+
+    // Ties go to the slope that was checked first.
+    fn fewest_trees(&self) -> Option<(Slope, u32)> {
+        self.counts
+            .iter()
+            .fold(None, |acc, (slope, trees)| match acc {
+                Some((_, fewest)) if fewest <= *trees => acc,
+                _ => Some((*slope, *trees)),
+            })
+    }
+
+    fn most_trees(&self) -> Option<(Slope, u32)> {
+        self.counts
+            .iter()
+            .fold(None, |acc, (slope, trees)| match acc {
+                Some((_, most)) if most >= *trees => acc,
+                _ => Some((*slope, *trees)),
+            })
+    }
+}
+
+// How a traversal handles moving past the edges of the map.
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum EdgeMode {
+    // Wrap around horizontally, stop once past the top or bottom.
+    WrapX,
+    // Wrap around both ways. Stops once it gets back to where it started
+    // since the path repeats from there.
+    WrapBoth,
+    // Stay on the edge instead of going past it. Stops once a step doesn't
+    // move anywhere.
+    Clamp,
+    // Stop as soon as it goes past any edge.
+    Stop,
+}
+
+enum AxisMode {
+    Wrap,
+    Clamp,
+    Stop,
+}
+
+impl AxisMode {
+    fn resolve(&self, pos: i64, size: i64) -> Option<i64> {
+        match self {
+            AxisMode::Wrap => Some(pos.rem_euclid(size)),
+            AxisMode::Clamp => Some(pos.max(0).min(size - 1)),
+            AxisMode::Stop if pos < 0 || pos >= size => None,
+            AxisMode::Stop => Some(pos),
+        }
+    }
+
+    // How many times pos goes around the axis when it wraps.
+    fn wraps(&self, pos: i64, size: i64) -> i64 {
+        match self {
+            AxisMode::Wrap => pos.div_euclid(size),
+            _ => 0,
+        }
+    }
+}
+
+impl EdgeMode {
+    fn parse(s: &str) -> Result<EdgeMode, err::ParseError> {
+        match s.trim().to_lowercase().as_str() {
+            "wrapx" => Ok(EdgeMode::WrapX),
+            "wrapboth" => Ok(EdgeMode::WrapBoth),
+            "clamp" => Ok(EdgeMode::Clamp),
+            "stop" => Ok(EdgeMode::Stop),
+            _ => Err(err::ParseError::new("unknown edge mode", s)),
+        }
+    }
+
+    fn axes(&self) -> (AxisMode, AxisMode) {
+        match self {
+            EdgeMode::WrapX => (AxisMode::Wrap, AxisMode::Stop),
+            EdgeMode::WrapBoth => (AxisMode::Wrap, AxisMode::Wrap),
+            EdgeMode::Clamp => (AxisMode::Clamp, AxisMode::Clamp),
+            EdgeMode::Stop => (AxisMode::Stop, AxisMode::Stop),
+        }
+    }
+}
+
 struct Traversal<'a> {
     map: &'a Map,
     movement: Movement,
+    edges: EdgeMode,
 
     loc_x: u32,
     loc_y: u32,
+    // Which copy of the map the traversal is on after wrapping horizontally.
+    tile: i64,
+    done: bool,
 }
 
 impl<'a> Traversal<'a> {
     fn new(map: &'a Map, movement: Movement) -> Traversal<'a> {
+        Traversal::with_edges(map, movement, EdgeMode::WrapX)
+    }
+
+    fn with_edges(map: &'a Map, movement: Movement, edges: EdgeMode) -> Traversal<'a> {
         Traversal {
-            map: map,
-            movement: movement,
+            map,
+            movement,
+            edges,
 
             loc_x: 0,
             loc_y: 0,
+            tile: 0,
+            done: false,
         }
     }
 
@@ -101,51 +445,74 @@ impl<'a> Traversal<'a> {
     fn y(&self) -> u32 {
         self.loc_y
     }
-}
 
-impl<'a> Iterator for Traversal<'a> {
-    type Item = &'a MapElement;
+    // The x position as if the map were repeated instead of wrapped.
+    fn unwrapped_x(&self) -> i64 {
+        self.tile * self.map.width() as i64 + self.x() as i64
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    fn step(&self) -> Option<(u32, u32, i64)> {
         let map_width = self.map.width() as i64;
         let map_height = self.map.height() as i64;
+        if map_width == 0 || map_height == 0 {
+            return None;
+        }
 
+        let (x_mode, y_mode) = self.edges.axes();
         let mut x = self.x() as i64;
         let mut y = self.y() as i64;
+        let mut tile = self.tile;
         for mov in self.movement.moves() {
-            match mov {
+            let next_x = match *mov {
                 Move::Up(delta) => {
-                    y = match y - (*delta as i64) {
-                        next_y if next_y < 0 => return None,
-                        next_y => next_y,
-                    }
+                    y = y_mode.resolve(y - delta as i64, map_height)?;
+                    continue;
                 }
                 Move::Down(delta) => {
-                    y = match y + (*delta as i64) {
-                        next_y if next_y > map_height => return None,
-                        next_y => next_y,
-                    }
-                }
-                Move::Left(delta) => {
-                    x = match x - (*delta as i64) {
-                        next_x if next_x < 0 => map_width - next_x.abs(),
-                        next_x => next_x,
-                    }
-                }
-                Move::Right(delta) => {
-                    x = match x + (*delta as i64) {
-                        next_x if next_x >= map_width => next_x - map_width,
-                        next_x => next_x,
-                    }
+                    y = y_mode.resolve(y + delta as i64, map_height)?;
+                    continue;
                 }
+                Move::Left(delta) => x - delta as i64,
+                Move::Right(delta) => x + delta as i64,
             };
+            tile += x_mode.wraps(next_x, map_width);
+            x = x_mode.resolve(next_x, map_width)?;
         }
 
-        self.loc_x = x as u32;
-        self.loc_y = y as u32;
+        let (x, y) = (x as u32, y as u32);
+        let finished = match self.edges {
+            EdgeMode::WrapBoth => x == 0 && y == 0,
+            EdgeMode::Clamp => x == self.x() && y == self.y(),
+            _ => false,
+        };
+        if finished {
+            return None;
+        }
 
-        let res = self.map.get(self.x() as usize, self.y() as usize);
-        res
+        Some((x, y, tile))
+    }
+}
+
+impl<'a> Iterator for Traversal<'a> {
+    type Item = &'a MapElement;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.step() {
+            Some((x, y, tile)) => {
+                self.loc_x = x;
+                self.loc_y = y;
+                self.tile = tile;
+                self.map.get(x as usize, y as usize)
+            }
+            None => {
+                self.done = true;
+                None
+            }
+        }
     }
 }
 
@@ -153,6 +520,8 @@ impl<'a> Iterator for Traversal<'a> {
 enum MapElement {
     Open,
     Tree,
+    // Anything else a legend defines, by its symbol.
+    Other(char),
 }
 
 impl MapElement {
@@ -163,72 +532,583 @@ impl MapElement {
             _ => Err(err::ParseError::new("unknown element", &d.to_string())),
         }
     }
-}
 
-impl std::fmt::Display for MapElement {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                MapElement::Tree => "#",
-                MapElement::Open => ".",
-            }
-        )
+    fn symbol(&self) -> char {
+        match self {
+            MapElement::Open => '.',
+            MapElement::Tree => '#',
+            MapElement::Other(c) => *c,
+        }
+    }
+}
+
+impl std::fmt::Display for MapElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+#[derive(PartialEq, Debug)]
+struct LegendEntry {
+    symbol: char,
+    name: String,
+    weight: i64,
+}
+
+// Names and weights for each kind of square a map can have.
+#[derive(PartialEq, Debug)]
+struct Legend {
+    entries: Vec<LegendEntry>,
+}
+
+impl Default for Legend {
+    fn default() -> Legend {
+        Legend {
+            entries: vec![
+                LegendEntry {
+                    symbol: '.',
+                    name: String::from("open"),
+                    weight: 0,
+                },
+                LegendEntry {
+                    symbol: '#',
+                    name: String::from("tree"),
+                    weight: 1,
+                },
+            ],
+        }
+    }
+}
+
+impl Legend {
+    // Parses one "symbol name weight" entry per line, like "# tree 1".
+    fn parse(s: &str) -> Result<Legend, err::ParseError> {
+        let mut entries: Vec<LegendEntry> = Vec::new();
+        for line in s.split('\n').map(|l| l.trim()).filter(|l| !l.is_empty()) {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() != 3 || parts[0].chars().count() != 1 {
+                return Err(err::ParseError::new("invalid legend format", line));
+            }
+
+            let symbol = parts[0].chars().next().unwrap_or_default();
+            if entries.iter().any(|e| e.symbol == symbol) {
+                return Err(err::ParseError::new("duplicate legend symbol", line));
+            }
+            let weight = match parts[2].parse::<i64>() {
+                Ok(weight) => weight,
+                Err(_) => return Err(err::ParseError::new("invalid legend weight", line)),
+            };
+
+            entries.push(LegendEntry {
+                symbol,
+                name: parts[1].to_owned(),
+                weight,
+            });
+        }
+
+        Ok(Legend { entries })
+    }
+
+    fn lookup(&self, symbol: char) -> Option<&LegendEntry> {
+        self.entries.iter().find(|e| e.symbol == symbol)
+    }
+
+    fn element(&self, d: char) -> Result<MapElement, err::ParseError> {
+        if self.lookup(d).is_none() {
+            return Err(err::ParseError::new("unknown element", &d.to_string()));
+        }
+        Ok(MapElement::parse(d).unwrap_or(MapElement::Other(d)))
+    }
+
+    // Elements with a positive weight are in the way, and are what slopes
+    // and paths count. Ones the legend doesn't know about never are.
+    fn is_obstacle(&self, element: &MapElement) -> bool {
+        self.lookup(element.symbol()).is_some_and(|e| e.weight > 0)
+    }
+}
+
+#[derive(PartialEq, Debug)]
+struct TraversalSummary {
+    // Landings on each element in legend order, including ones never hit.
+    counts: Vec<(String, u32)>,
+    cost: i64,
+}
+
+struct Map {
+    grid: Grid<MapElement>,
+    legend: Legend,
+}
+
+impl Map {
+    fn parse(d: &str) -> Result<Map, err::ParseError> {
+        Map::parse_with_legend(d, Legend::default())
+    }
+
+    fn parse_with_legend(d: &str, legend: Legend) -> Result<Map, err::ParseError> {
+        Ok(Map {
+            grid: Grid::parse(d, |c| legend.element(c))?,
+            legend,
+        })
+    }
+
+    fn height(&self) -> usize {
+        self.grid.height()
+    }
+
+    fn width(&self) -> usize {
+        self.grid.width()
+    }
+
+    fn get(&self, x: usize, y: usize) -> Option<&MapElement> {
+        self.grid.get(x, y)
+    }
+
+    fn traverse(&self, movement: Movement) -> Traversal<'_> {
+        Traversal::new(self, movement)
+    }
+
+    fn obstacles(&self, movement: Movement) -> u32 {
+        self.traverse(movement)
+            .filter(|e| self.legend.is_obstacle(e))
+            .count() as u32
+    }
+
+    fn summarize(&self, movement: Movement) -> TraversalSummary {
+        let mut counts: Vec<(String, u32)> = self
+            .legend
+            .entries
+            .iter()
+            .map(|e| (e.name.clone(), 0))
+            .collect();
+        let mut cost = 0;
+
+        for element in self.traverse(movement) {
+            let symbol = element.symbol();
+            if let Some(idx) = self.legend.entries.iter().position(|e| e.symbol == symbol) {
+                counts[idx].1 += 1;
+                cost += self.legend.entries[idx].weight;
+            }
+        }
+
+        TraversalSummary { counts, cost }
+    }
+
+    fn slope_trees(&self, slope: &Slope) -> u32 {
+        self.obstacles(slope.movement())
+    }
+
+    fn slope_product(&self, slopes: &[Slope]) -> Result<u64, err::OverflowError> {
+        let counts: Vec<u64> = slopes
+            .par_iter()
+            .map(|slope| self.slope_trees(slope) as u64)
+            .collect();
+        counts
+            .iter()
+            .try_fold(1u64, |acc, trees| acc.checked_mul(*trees))
+            .ok_or_else(|| {
+                err::OverflowError::new(
+                    "product",
+                    &format!("trees on {} slopes", slopes.len()),
+                    "u64",
+                )
+            })
+    }
+
+    // Checks every combination of right and down moves in the ranges,
+    // ordered by right then down.
+    fn search_slopes(&self, rights: Range<u32>, downs: Range<u32>) -> SlopeReport {
+        let slopes: Vec<Slope> = rights
+            .flat_map(|right| downs.clone().map(move |down| Slope::new(right, down)))
+            .collect();
+
+        SlopeReport {
+            counts: slopes
+                .into_par_iter()
+                .map(|slope| (slope, self.slope_trees(&slope)))
+                .collect(),
+        }
+    }
+
+    fn render_path(&self, movement: Movement, edges: EdgeMode) -> PathRender {
+        let width = self.width() as i64;
+
+        let mut visits = Vec::new();
+        let mut t = Traversal::with_edges(self, movement, edges);
+        while t.next().is_some() {
+            visits.push((t.unwrapped_x(), t.y() as usize));
+        }
+
+        // Repeat the map far enough to cover every copy the path went
+        // through, including the starting one.
+        let first_tile = visits
+            .iter()
+            .map(|(x, _)| x.div_euclid(width.max(1)))
+            .fold(0, |acc, tile| acc.min(tile));
+        let last_tile = visits
+            .iter()
+            .map(|(x, _)| x.div_euclid(width.max(1)))
+            .fold(0, |acc, tile| acc.max(tile));
+        let left = first_tile * width;
+
+        let rows = (0..self.height())
+            .map(|y| {
+                (0..width * (last_tile - first_tile + 1))
+                    .map(|x| {
+                        let element = self.get((x + left).rem_euclid(width) as usize, y);
+                        match element {
+                            Some(e) if self.legend.is_obstacle(e) => PathMark::Obstacle(e.symbol()),
+                            Some(MapElement::Open) | None => PathMark::Open,
+                            Some(e) => PathMark::Other(e.symbol()),
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut grid = match Grid::from_rows(rows) {
+            Ok(grid) => grid,
+            Err(e) => panic!("{:?}", e),
+        };
+        for (x, y) in visits {
+            if let Some(mark) = grid.get_mut((x - left) as usize, y) {
+                *mark = match mark {
+                    PathMark::Obstacle(_) | PathMark::Hit => PathMark::Hit,
+                    _ => PathMark::Visited,
+                };
+            }
+        }
+
+        PathRender { grid }
+    }
+
+    fn find_path(&self, moves: &[Move], wrap: bool, search: PathSearch) -> Option<FoundPath> {
+        if self.width() == 0 || self.height() == 0 {
+            return None;
+        }
+
+        let finder = PathFinder {
+            map: self,
+            moves,
+            wrap,
+        };
+        match search {
+            PathSearch::Bfs => finder.bfs(),
+            PathSearch::Dijkstra => finder.best_first(false),
+            PathSearch::AStar => finder.best_first(true),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum PathMark {
+    Open,
+    Obstacle(char),
+    Other(char),
+    Visited,
+    Hit,
+}
+
+impl PathMark {
+    fn rgb(&self) -> (u8, u8, u8) {
+        match self {
+            PathMark::Open => (255, 255, 255),
+            PathMark::Obstacle(_) => (34, 120, 50),
+            PathMark::Other(_) => (160, 160, 160),
+            PathMark::Visited => (60, 110, 220),
+            PathMark::Hit => (220, 40, 40),
+        }
+    }
+}
+
+impl std::fmt::Display for PathMark {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathMark::Open => write!(f, "{}", MapElement::Open),
+            PathMark::Obstacle(c) | PathMark::Other(c) => write!(f, "{}", c),
+            PathMark::Visited => write!(f, "O"),
+            PathMark::Hit => write!(f, "X"),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum RenderFormat {
+    Text,
+    Ansi,
+    Svg,
+    Ppm,
+}
+
+impl RenderFormat {
+    fn parse(s: &str) -> Result<RenderFormat, err::ParseError> {
+        match s.trim().to_lowercase().as_str() {
+            "text" => Ok(RenderFormat::Text),
+            "ansi" => Ok(RenderFormat::Ansi),
+            "svg" => Ok(RenderFormat::Svg),
+            "ppm" => Ok(RenderFormat::Ppm),
+            _ => Err(err::ParseError::new("unknown render format", s)),
+        }
+    }
+}
+
+// A map repeated horizontally as far as a traversal went, with the squares
+// it landed on marked.
+struct PathRender {
+    grid: Grid<PathMark>,
+}
+
+impl PathRender {
+    const CELL_SIZE: usize = 4;
+
+    fn render(&self, format: RenderFormat) -> String {
+        match format {
+            RenderFormat::Text => self.grid.to_string(),
+            RenderFormat::Ansi => self.render_ansi(),
+            RenderFormat::Svg => self.render_svg(),
+            RenderFormat::Ppm => self.render_ppm(),
+        }
+    }
+
+    fn render_ansi(&self) -> String {
+        let mut out = String::new();
+        for row in self.grid.rows() {
+            for mark in row {
+                out += &match mark {
+                    PathMark::Open | PathMark::Other(_) => mark.to_string(),
+                    PathMark::Obstacle(_) => format!("\x1b[32m{}\x1b[0m", mark),
+                    PathMark::Visited => format!("\x1b[1;34m{}\x1b[0m", mark),
+                    PathMark::Hit => format!("\x1b[1;31m{}\x1b[0m", mark),
+                };
+            }
+            out += "\n";
+        }
+        out
+    }
+
+    fn render_svg(&self) -> String {
+        let size = PathRender::CELL_SIZE;
+        let mut out = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}">"#,
+            self.grid.width() * size,
+            self.grid.height() * size
+        );
+        out += "\n";
+        for (x, y, mark) in self.grid.iter() {
+            let (r, g, b) = mark.rgb();
+            out += &format!(
+                r#"<rect x="{}" y="{}" width="{}" height="{}" fill="rgb({},{},{})"/>"#,
+                x * size,
+                y * size,
+                size,
+                size,
+                r,
+                g,
+                b
+            );
+            out += "\n";
+        }
+        out += "</svg>\n";
+        out
+    }
+
+    // Plain (P3) PPM with one pixel per square.
+    fn render_ppm(&self) -> String {
+        let mut out = format!("P3\n{} {}\n255\n", self.grid.width(), self.grid.height());
+        for row in self.grid.rows() {
+            let pixels: Vec<String> = row
+                .iter()
+                .map(|mark| {
+                    let (r, g, b) = mark.rgb();
+                    format!("{} {} {}", r, g, b)
+                })
+                .collect();
+            out += &pixels.join(" ");
+            out += "\n";
+        }
+        out
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum PathSearch {
+    // Fewest moves, ignoring trees.
+    Bfs,
+    // Fewest trees, then fewest moves.
+    Dijkstra,
+    // Same result as Dijkstra, guided by the rows left to cover.
+    AStar,
+}
+
+impl PathSearch {
+    fn parse(s: &str) -> Result<PathSearch, err::ParseError> {
+        match s.trim().to_lowercase().as_str() {
+            "bfs" => Ok(PathSearch::Bfs),
+            "dijkstra" => Ok(PathSearch::Dijkstra),
+            "astar" | "a*" => Ok(PathSearch::AStar),
+            _ => Err(err::ParseError::new("unknown path search", s)),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+struct FoundPath {
+    // Every square from the top row to the bottom row, including both ends.
+    path: Vec<(usize, usize)>,
+    trees: u32,
+}
+
+// Searches the squares of a map as a graph where each allowed move is an
+// edge, starting anywhere on the top row and ending anywhere on the bottom.
+struct PathFinder<'a> {
+    map: &'a Map,
+    moves: &'a [Move],
+    wrap: bool,
+}
+
+impl<'a> PathFinder<'a> {
+    fn idx(&self, x: usize, y: usize) -> usize {
+        y * self.map.width() + x
+    }
+
+    fn pos(&self, idx: usize) -> (usize, usize) {
+        (idx % self.map.width(), idx / self.map.width())
+    }
+
+    fn tree_cost(&self, idx: usize) -> u32 {
+        let (x, y) = self.pos(idx);
+        match self.map.get(x, y) {
+            Some(e) if self.map.legend.is_obstacle(e) => 1,
+            _ => 0,
+        }
+    }
+
+    fn is_goal(&self, idx: usize) -> bool {
+        self.pos(idx).1 == self.map.height() - 1
     }
-}
 
-struct Map {
-    map: Vec<Vec<MapElement>>,
-}
+    fn next(&self, idx: usize) -> Vec<usize> {
+        let (width, height) = (self.map.width() as i64, self.map.height() as i64);
+        let (x, y) = self.pos(idx);
 
-impl Map {
-    fn parse(d: &str) -> Result<Map, err::ParseError> {
-        let lines: Vec<&str> = d
-            .split("\n")
-            .into_iter()
-            .map(|l| l.trim())
-            .filter(|l| *l != "")
-            .collect();
-        let mut map: Vec<Vec<MapElement>> = Vec::new();
-        for line in lines {
-            let mut map_line = Vec::new();
-            for c in line.chars() {
-                map_line.push(MapElement::parse(c)?)
+        let mut found = Vec::new();
+        for mov in self.moves {
+            let (dx, dy) = mov.delta();
+            let (mut nx, ny) = (x as i64 + dx, y as i64 + dy);
+            if self.wrap {
+                nx = nx.rem_euclid(width);
+            }
+            if nx < 0 || nx >= width || ny < 0 || ny >= height {
+                continue;
+            }
+            let next_idx = self.idx(nx as usize, ny as usize);
+            if next_idx != idx {
+                found.push(next_idx);
             }
-            map.push(map_line);
         }
+        found
+    }
 
-        Ok(Map { map: map })
+    // Fewest rows any single move can cover, used as the A* heuristic.
+    fn min_moves_left(&self, idx: usize) -> u32 {
+        let max_down = self
+            .moves
+            .iter()
+            .map(|m| m.delta().1)
+            .fold(0, |acc, dy| acc.max(dy));
+        let rows_left = (self.map.height() - 1 - self.pos(idx).1) as i64;
+        match max_down {
+            0 => 0,
+            _ => ((rows_left + max_down - 1) / max_down) as u32,
+        }
     }
 
-    fn height(&self) -> usize {
-        self.map.len()
+    fn starts(&self) -> Vec<usize> {
+        (0..self.map.width()).map(|x| self.idx(x, 0)).collect()
     }
 
-    fn width(&self) -> usize {
-        match self.map.first() {
-            Some(l) => l.len(),
-            None => 0,
+    fn build_path(&self, prev: &[Option<usize>], end: usize) -> FoundPath {
+        let mut path = vec![self.pos(end)];
+        let mut trees = self.tree_cost(end);
+        let mut current = end;
+        while let Some(p) = prev[current] {
+            path.push(self.pos(p));
+            trees += self.tree_cost(p);
+            current = p;
         }
+        path.reverse();
+
+        FoundPath { path, trees }
     }
 
-    fn get(&self, x: usize, y: usize) -> Option<&MapElement> {
-        match self.map.get(y) {
-            Some(l) => match l.get(x) {
-                Some(me) => Some(me),
-                _ => None,
-            },
-            _ => None,
+    fn bfs(&self) -> Option<FoundPath> {
+        let mut prev = vec![None; self.map.width() * self.map.height()];
+        let mut seen = vec![false; prev.len()];
+        let mut queue = VecDeque::new();
+        for start in self.starts() {
+            seen[start] = true;
+            queue.push_back(start);
         }
-    }
 
-    fn traverse(&self, movement: Movement) -> Traversal {
-        Traversal::new(self, movement)
+        while let Some(idx) = queue.pop_front() {
+            if self.is_goal(idx) {
+                return Some(self.build_path(&prev, idx));
+            }
+            for next in self.next(idx) {
+                if !seen[next] {
+                    seen[next] = true;
+                    prev[next] = Some(idx);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        None
     }
 
-    fn encounters(&self, movement: Movement, element: MapElement) -> u32 {
-        self.traverse(movement).filter(|e| **e == element).count() as u32
+    // Dijkstra over (trees, moves) costs. With a heuristic it becomes A*,
+    // which only ever underestimates the moves left so the result matches.
+    fn best_first(&self, heuristic: bool) -> Option<FoundPath> {
+        let estimate = |idx: usize| {
+            if heuristic {
+                self.min_moves_left(idx)
+            } else {
+                0
+            }
+        };
+
+        let mut prev = vec![None; self.map.width() * self.map.height()];
+        let mut best: Vec<Option<(u32, u32)>> = vec![None; prev.len()];
+        let mut heap = BinaryHeap::new();
+        for start in self.starts() {
+            let cost = (self.tree_cost(start), 0);
+            best[start] = Some(cost);
+            heap.push(Reverse(((cost.0, cost.1 + estimate(start)), cost, start)));
+        }
+
+        while let Some(Reverse((_, cost, idx))) = heap.pop() {
+            if best[idx] != Some(cost) {
+                continue;
+            }
+            if self.is_goal(idx) {
+                return Some(self.build_path(&prev, idx));
+            }
+
+            for next in self.next(idx) {
+                let next_cost = (cost.0 + self.tree_cost(next), cost.1 + 1);
+                let better = match best[next] {
+                    Some(known) => next_cost < known,
+                    None => true,
+                };
+                if better {
+                    best[next] = Some(next_cost);
+                    prev[next] = Some(idx);
+                    let priority = (next_cost.0, next_cost.1 + estimate(next));
+                    heap.push(Reverse((priority, next_cost, next)));
+                }
+            }
+        }
+
+        None
     }
 }
 
@@ -372,5 +1252,622 @@ mod tests {
             assert_eq!(t.next(), Some(&MapElement::Tree)); // 10
             assert_eq!(t.next(), None);
         }
+
+        #[test]
+        fn down_stops_at_bottom() {
+            let map = make_test_map_11x11();
+            let mut t = Traversal::new(&map, Movement::with_moves(vec![Move::Down(1)]));
+
+            assert_eq!(t.by_ref().count(), 10);
+            assert_eq!(t.y(), 10);
+            assert_eq!(t.next(), None);
+        }
+        #[test]
+        fn up_stops_at_top() {
+            let map = make_test_map_11x11();
+            let mut t = Traversal::new(&map, Movement::with_moves(vec![Move::Up(1)]));
+
+            assert_eq!(t.next(), None);
+            assert_eq!(t.y(), 0);
+        }
+        #[test]
+        fn stop_at_any_edge() {
+            let map = make_test_map_11x11();
+            let mut t = Traversal::with_edges(
+                &map,
+                Movement::with_moves(vec![Move::Right(3), Move::Down(1)]),
+                EdgeMode::Stop,
+            );
+
+            assert_eq!(t.by_ref().count(), 3);
+            assert_eq!((t.x(), t.y()), (9, 3));
+        }
+        #[test]
+        fn clamp_to_corner() {
+            let map = make_test_map_11x11();
+            let mut t = Traversal::with_edges(
+                &map,
+                Movement::with_moves(vec![Move::Right(3), Move::Down(1)]),
+                EdgeMode::Clamp,
+            );
+
+            assert_eq!(t.by_ref().count(), 10);
+            assert_eq!((t.x(), t.y()), (10, 10));
+        }
+        #[test]
+        fn wrap_both_until_start() {
+            let map = make_test_map_11x11();
+            let mut t = Traversal::with_edges(
+                &map,
+                Movement::with_moves(vec![Move::Right(3), Move::Down(1)]),
+                EdgeMode::WrapBoth,
+            );
+
+            assert_eq!(t.by_ref().count(), 10);
+            assert_eq!((t.x(), t.y()), (8, 10));
+        }
+
+        fn gcd(a: u32, b: u32) -> u32 {
+            match b {
+                0 => a,
+                _ => gcd(b, a % b),
+            }
+        }
+
+        #[test]
+        fn prop_wrap_x_any_delta() {
+            for map in &[make_test_map_11x11(), make_test_map_12x12()] {
+                let width = map.width() as i64;
+                for delta in 0..60 {
+                    let mut right = Traversal::new(
+                        map,
+                        Movement::with_moves(vec![Move::Right(delta), Move::Down(1)]),
+                    );
+                    let mut left = Traversal::new(
+                        map,
+                        Movement::with_moves(vec![Move::Left(delta), Move::Down(1)]),
+                    );
+                    for step in 1..map.height() as i64 {
+                        assert!(right.next().is_some());
+                        assert!(left.next().is_some());
+                        let moved = step * delta as i64;
+                        assert_eq!(right.x() as i64, moved.rem_euclid(width));
+                        assert_eq!(left.x() as i64, (-moved).rem_euclid(width));
+                        assert_eq!(right.y() as i64, step);
+                    }
+                    assert_eq!(right.next(), None);
+                    assert_eq!(left.next(), None);
+                }
+            }
+        }
+
+        #[test]
+        fn prop_wrap_both_cycle_length() {
+            let map = make_test_map_12x12();
+            let (width, height) = (map.width() as u32, map.height() as u32);
+            for dx in 0..30 {
+                for dy in 1..30 {
+                    if dy % height == 0 && dx % width == 0 {
+                        continue;
+                    }
+                    let x_period = width / gcd(width, dx % width);
+                    let y_period = height / gcd(height, dy % height);
+                    let period = x_period * y_period / gcd(x_period, y_period);
+
+                    let t = Traversal::with_edges(
+                        &map,
+                        Movement::with_moves(vec![Move::Right(dx), Move::Down(dy)]),
+                        EdgeMode::WrapBoth,
+                    );
+                    assert_eq!(t.count() as u32, period - 1, "dx {} dy {}", dx, dy);
+                }
+            }
+        }
+
+        #[test]
+        fn prop_clamp_in_bounds() {
+            let map = make_test_map_11x11();
+            for dx in 0..30 {
+                for dy in 0..30 {
+                    let mut t = Traversal::with_edges(
+                        &map,
+                        Movement::with_moves(vec![Move::Right(dx), Move::Down(dy)]),
+                        EdgeMode::Clamp,
+                    );
+                    // It has to settle within the larger of the map sides.
+                    for _ in 0..=map.width().max(map.height()) {
+                        match t.next() {
+                            Some(_) => {
+                                assert!((t.x() as usize) < map.width());
+                                assert!((t.y() as usize) < map.height());
+                            }
+                            None => break,
+                        }
+                    }
+                    assert_eq!(t.next(), None, "dx {} dy {}", dx, dy);
+                }
+            }
+        }
+
+        #[test]
+        fn prop_stop_in_bounds() {
+            let map = make_test_map_11x11();
+            for dx in 0..30 {
+                for dy in 1..30 {
+                    let t = Traversal::with_edges(
+                        &map,
+                        Movement::with_moves(vec![Move::Right(dx), Move::Down(dy)]),
+                        EdgeMode::Stop,
+                    );
+                    let expected = match dx {
+                        0 => 10 / dy,
+                        _ => (10 / dx).min(10 / dy),
+                    };
+                    assert_eq!(t.count() as u32, expected, "dx {} dy {}", dx, dy);
+                }
+            }
+        }
+    }
+
+    mod slopes {
+        use super::*;
+
+        #[test]
+        fn parse() {
+            assert_eq!(Slope::parse("3,1"), Ok(Slope::new(3, 1)));
+            assert_eq!(Slope::parse(" 7 , 2 "), Ok(Slope::new(7, 2)));
+            assert_eq!(
+                Slope::parse("3"),
+                Err(err::ParseError::new("invalid slope format", "3")),
+            );
+            assert_eq!(
+                Slope::parse("3,x"),
+                Err(err::ParseError::new("invalid slope value", "3,x")),
+            );
+            assert_eq!(
+                Slope::parse("3,0"),
+                Err(err::ParseError::new("slope has to move down", "3,0")),
+            );
+        }
+
+        #[test]
+        fn slope_trees() {
+            let map = make_test_map_11x11();
+            assert_eq!(map.slope_trees(&Slope::new(1, 1)), 2);
+            assert_eq!(map.slope_trees(&Slope::new(3, 1)), 7);
+            assert_eq!(map.slope_trees(&Slope::new(5, 1)), 3);
+            assert_eq!(map.slope_trees(&Slope::new(7, 1)), 4);
+            assert_eq!(map.slope_trees(&Slope::new(1, 2)), 2);
+        }
+
+        #[test]
+        fn slope_product() {
+            let map = make_test_map_11x11();
+            assert_eq!(
+                map.slope_product(&[
+                    Slope::new(1, 1),
+                    Slope::new(3, 1),
+                    Slope::new(5, 1),
+                    Slope::new(7, 1),
+                    Slope::new(1, 2),
+                ]),
+                Ok(336),
+            );
+            assert_eq!(map.slope_product(&[]), Ok(1));
+        }
+
+        #[test]
+        fn slope_product_overflow() {
+            let map = make_test_map_11x11();
+            let slopes = vec![Slope::new(3, 1); 23];
+            assert_eq!(map.slope_product(&slopes[..22]), Ok(7u64.pow(22)),);
+            assert_eq!(
+                map.slope_product(&slopes),
+                Err(err::OverflowError::new(
+                    "product",
+                    "trees on 23 slopes",
+                    "u64"
+                )),
+            );
+        }
+
+        #[test]
+        fn search_slopes() {
+            let map = make_test_map_11x11();
+            let report = map.search_slopes(0..11, 1..11);
+            assert_eq!(report.counts.len(), 110);
+            assert_eq!(report.counts[0], (Slope::new(0, 1), 3));
+            assert_eq!(report.counts[21], (Slope::new(2, 2), 1));
+            assert_eq!(report.fewest_trees(), Some((Slope::new(0, 5), 0)));
+            assert_eq!(report.most_trees(), Some((Slope::new(3, 1), 7)));
+        }
+
+        #[test]
+        fn search_slopes_empty() {
+            let map = make_test_map_11x11();
+            let report = map.search_slopes(0..0, 1..11);
+            assert_eq!(report.counts, vec![]);
+            assert_eq!(report.fewest_trees(), None);
+        }
+
+        #[test]
+        fn by_slope() {
+            let report = SlopeReport {
+                counts: vec![
+                    (Slope::new(3, 1), 7),
+                    (Slope::new(1, 2), 2),
+                    (Slope::new(1, 1), 2),
+                ],
+            };
+            assert_eq!(
+                report.by_slope(),
+                vec![
+                    (Slope::new(1, 1), 2),
+                    (Slope::new(1, 2), 2),
+                    (Slope::new(3, 1), 7),
+                ],
+            );
+        }
+    }
+
+    mod render {
+        use super::*;
+
+        #[test]
+        fn render_text() {
+            let map = make_test_map_11x11();
+            let render = map.render_path(Slope::new(3, 1).movement(), EdgeMode::WrapX);
+            assert_eq!(
+                render.render(RenderFormat::Text),
+                "..##.........##.........##.......\n\
+                 #..O#...#..#...#...#..#...#...#..\n\
+                 .#....X..#..#....#..#..#....#..#.\n\
+                 ..#.#...#O#..#.#...#.#..#.#...#.#\n\
+                 .#...##..#..X...##..#..#...##..#.\n\
+                 ..#.##.......#.X#.......#.##.....\n\
+                 .#.#.#....#.#.#.#.O..#.#.#.#....#\n\
+                 .#........#.#........X.#........#\n\
+                 #.##...#...#.##...#...#.X#...#...\n\
+                 #...##....##...##....##...#X....#\n\
+                 .#..#...#.#.#..#...#.#.#..#...X.#\n",
+            );
+        }
+
+        #[test]
+        fn render_left() {
+            let map = Map::parse(".#\n..\n##\n").unwrap();
+            let render = map.render_path(
+                Movement::with_moves(vec![Move::Left(1), Move::Down(1)]),
+                EdgeMode::WrapX,
+            );
+            assert_eq!(render.render(RenderFormat::Text), ".#.#\n.O..\nX###\n");
+        }
+
+        #[test]
+        fn render_ansi() {
+            let map = Map::parse(".#\n#.\n").unwrap();
+            let render = map.render_path(
+                Movement::with_moves(vec![Move::Right(1), Move::Down(1)]),
+                EdgeMode::WrapX,
+            );
+            assert_eq!(
+                render.render(RenderFormat::Ansi),
+                ".\x1b[32m#\x1b[0m\n\x1b[32m#\x1b[0m\x1b[1;34mO\x1b[0m\n",
+            );
+        }
+
+        #[test]
+        fn render_ppm() {
+            let map = Map::parse(".#\n#.\n").unwrap();
+            let render = map.render_path(
+                Movement::with_moves(vec![Move::Right(1), Move::Down(1)]),
+                EdgeMode::WrapX,
+            );
+            assert_eq!(
+                render.render(RenderFormat::Ppm),
+                "P3\n2 2\n255\n255 255 255 34 120 50\n34 120 50 60 110 220\n",
+            );
+        }
+
+        #[test]
+        fn render_svg() {
+            let map = Map::parse("#\n").unwrap();
+            let render =
+                map.render_path(Movement::with_moves(vec![Move::Down(1)]), EdgeMode::WrapX);
+            assert_eq!(
+                render.render(RenderFormat::Svg),
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"4\" height=\"4\">\n\
+                 <rect x=\"0\" y=\"0\" width=\"4\" height=\"4\" fill=\"rgb(34,120,50)\"/>\n\
+                 </svg>\n",
+            );
+        }
+
+        #[test]
+        fn render_format_parse() {
+            assert_eq!(RenderFormat::parse("SVG"), Ok(RenderFormat::Svg));
+            assert_eq!(
+                RenderFormat::parse("png"),
+                Err(err::ParseError::new("unknown render format", "png")),
+            );
+        }
+
+        #[test]
+        fn edge_mode_parse() {
+            assert_eq!(EdgeMode::parse("WrapBoth"), Ok(EdgeMode::WrapBoth));
+            assert_eq!(EdgeMode::parse(" clamp"), Ok(EdgeMode::Clamp));
+            assert_eq!(
+                EdgeMode::parse("bounce"),
+                Err(err::ParseError::new("unknown edge mode", "bounce")),
+            );
+        }
+    }
+
+    mod legend {
+        use super::*;
+
+        fn make_legend() -> Legend {
+            Legend::parse(
+                r"
+                . open 0
+                # tree 5
+                ~ ice -1
+                ",
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn parse() {
+            assert_eq!(
+                make_legend().lookup('~'),
+                Some(&LegendEntry {
+                    symbol: '~',
+                    name: String::from("ice"),
+                    weight: -1,
+                }),
+            );
+            assert_eq!(make_legend().lookup('x'), None);
+        }
+
+        #[test]
+        fn parse_errors() {
+            assert_eq!(
+                Legend::parse("~ ice"),
+                Err(err::ParseError::new("invalid legend format", "~ ice")),
+            );
+            assert_eq!(
+                Legend::parse("~~ ice 1"),
+                Err(err::ParseError::new("invalid legend format", "~~ ice 1")),
+            );
+            assert_eq!(
+                Legend::parse("~ ice 1\n~ snow 2"),
+                Err(err::ParseError::new("duplicate legend symbol", "~ snow 2")),
+            );
+            assert_eq!(
+                Legend::parse("~ ice x"),
+                Err(err::ParseError::new("invalid legend weight", "~ ice x")),
+            );
+        }
+
+        #[test]
+        fn map_with_legend() {
+            let map = Map::parse_with_legend(".#~\n~..\n", make_legend()).unwrap();
+            assert_eq!(map.get(2, 0), Some(&MapElement::Other('~')));
+            assert_eq!(map.get(1, 0), Some(&MapElement::Tree));
+            assert_eq!(
+                Map::parse_with_legend(".#x", make_legend()).err(),
+                Some(err::ParseError::new("unknown element", "x")),
+            );
+            assert_eq!(
+                Map::parse(".#~").err(),
+                Some(err::ParseError::new("unknown element", "~")),
+            );
+        }
+
+        #[test]
+        fn summarize() {
+            let map = Map::parse_with_legend(
+                r"
+                ....
+                .#..
+                ..~.
+                #..#
+                ~...
+                ",
+                make_legend(),
+            )
+            .unwrap();
+            let summary = map.summarize(Slope::new(1, 1).movement());
+            assert_eq!(
+                summary,
+                TraversalSummary {
+                    counts: vec![
+                        (String::from("open"), 0),
+                        (String::from("tree"), 2),
+                        (String::from("ice"), 2),
+                    ],
+                    cost: 8,
+                },
+            );
+        }
+
+        #[test]
+        fn summarize_default_legend() {
+            let map = make_test_map_11x11();
+            let summary = map.summarize(Slope::new(3, 1).movement());
+            assert_eq!(
+                summary.counts,
+                vec![(String::from("open"), 3), (String::from("tree"), 7)],
+            );
+            assert_eq!(summary.cost, 7);
+        }
+
+        #[test]
+        fn render_other() {
+            let map = Map::parse_with_legend("~~\n~~\n", make_legend()).unwrap();
+            let render = map.render_path(Slope::new(1, 1).movement(), EdgeMode::WrapX);
+            assert_eq!(render.render(RenderFormat::Text), "~~\n~O\n");
+        }
+
+        #[test]
+        fn obstacles_from_legend() {
+            let legend = Legend::parse(
+                r"
+                . snow 0
+                # open 0
+                ~ rock 2
+                ",
+            )
+            .unwrap();
+            let map = Map::parse_with_legend(
+                r"
+                #~#
+                #~.
+                #.~
+                ",
+                legend,
+            )
+            .unwrap();
+            assert_eq!(map.slope_trees(&Slope::new(1, 1)), 2);
+            assert_eq!(map.slope_trees(&Slope::new(2, 1)), 0);
+
+            let render = map.render_path(Slope::new(1, 1).movement(), EdgeMode::WrapX);
+            assert_eq!(render.render(RenderFormat::Text), "#~#\n#X.\n#.X\n");
+
+            let found = map
+                .find_path(&[Move::Down(1)], false, PathSearch::Dijkstra)
+                .unwrap();
+            assert_eq!(found.trees, 0);
+            assert_eq!(found.path, vec![(0, 0), (0, 1), (0, 2)]);
+        }
+    }
+
+    mod path {
+        use super::*;
+
+        fn assert_valid_path(map: &Map, found: &FoundPath, moves: &[Move], wrap: bool) {
+            assert_eq!(found.path.first().map(|p| p.1), Some(0));
+            assert_eq!(found.path.last().map(|p| p.1), Some(map.height() - 1));
+            for step in found.path.windows(2) {
+                let (from, to) = (step[0], step[1]);
+                assert!(
+                    moves.iter().any(|m| {
+                        let (dx, dy) = m.delta();
+                        let mut x = from.0 as i64 + dx;
+                        if wrap {
+                            x = x.rem_euclid(map.width() as i64);
+                        }
+                        (x, from.1 as i64 + dy) == (to.0 as i64, to.1 as i64)
+                    }),
+                    "{:?} -> {:?}",
+                    from,
+                    to,
+                );
+            }
+            let trees = found
+                .path
+                .iter()
+                .filter(|(x, y)| map.get(*x, *y) == Some(&MapElement::Tree))
+                .count();
+            assert_eq!(found.trees, trees as u32);
+        }
+
+        #[test]
+        fn parse_move() {
+            assert_eq!(Move::parse("r3"), Ok(Move::Right(3)));
+            assert_eq!(Move::parse("D1"), Ok(Move::Down(1)));
+            assert_eq!(Move::parse(" u10 "), Ok(Move::Up(10)));
+            assert_eq!(
+                Move::parse("x1"),
+                Err(err::ParseError::new("invalid move direction", "x1")),
+            );
+            assert_eq!(
+                Move::parse("l"),
+                Err(err::ParseError::new("invalid move distance", "l")),
+            );
+        }
+
+        #[test]
+        fn fewest_trees() {
+            let map = Map::parse("##.#\n#..#\n#.##\n##.#\n").unwrap();
+            let moves = [Move::Down(1), Move::Left(1), Move::Right(1)];
+
+            let expected = FoundPath {
+                path: vec![(2, 0), (2, 1), (2, 2), (2, 3)],
+                trees: 1,
+            };
+            assert_eq!(
+                map.find_path(&moves, false, PathSearch::Dijkstra),
+                Some(expected)
+            );
+            let found = map.find_path(&moves, false, PathSearch::AStar).unwrap();
+            assert_eq!(found.trees, 1);
+            assert_eq!(found.path.len(), 4);
+        }
+
+        #[test]
+        fn bfs_fewest_moves() {
+            let map = Map::parse("##.#\n#..#\n#.##\n##.#\n").unwrap();
+            let moves = [Move::Down(1), Move::Left(1), Move::Right(1)];
+
+            assert_eq!(
+                map.find_path(&moves, false, PathSearch::Bfs),
+                Some(FoundPath {
+                    path: vec![(0, 0), (0, 1), (0, 2), (0, 3)],
+                    trees: 4,
+                }),
+            );
+        }
+
+        #[test]
+        fn wrap_avoids_trees() {
+            let map = Map::parse("#.#\n...\n.##\n").unwrap();
+            let moves = [Move::Down(1), Move::Right(1)];
+
+            let found = map.find_path(&moves, false, PathSearch::Dijkstra).unwrap();
+            assert_eq!(found.trees, 1);
+            assert_eq!(
+                map.find_path(&moves, true, PathSearch::Dijkstra),
+                Some(FoundPath {
+                    path: vec![(1, 0), (1, 1), (2, 1), (0, 1), (0, 2)],
+                    trees: 0,
+                }),
+            );
+        }
+
+        #[test]
+        fn no_path() {
+            let map = make_test_map_11x11();
+            for search in &[PathSearch::Bfs, PathSearch::Dijkstra, PathSearch::AStar] {
+                assert_eq!(map.find_path(&[Move::Right(1)], true, *search), None);
+            }
+        }
+
+        #[test]
+        fn searches_agree() {
+            let maps = [make_test_map_11x11(), make_test_map_12x12()];
+            let move_sets = [
+                vec![Move::Down(1), Move::Left(1), Move::Right(1)],
+                vec![Move::Down(2), Move::Right(3), Move::Up(1)],
+                vec![Move::Down(1), Move::Right(1)],
+            ];
+            for map in &maps {
+                for moves in &move_sets {
+                    for wrap in &[false, true] {
+                        let dijkstra = map.find_path(moves, *wrap, PathSearch::Dijkstra).unwrap();
+                        let astar = map.find_path(moves, *wrap, PathSearch::AStar).unwrap();
+                        let bfs = map.find_path(moves, *wrap, PathSearch::Bfs).unwrap();
+                        assert_valid_path(map, &dijkstra, moves, *wrap);
+                        assert_valid_path(map, &astar, moves, *wrap);
+                        assert_valid_path(map, &bfs, moves, *wrap);
+
+                        assert_eq!(astar.trees, dijkstra.trees);
+                        assert_eq!(astar.path.len(), dijkstra.path.len());
+                        assert!(bfs.path.len() <= dijkstra.path.len());
+                        assert!(bfs.trees >= dijkstra.trees);
+                    }
+                }
+            }
+        }
     }
 }
@@ -1,25 +1,31 @@
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::prelude::*;
+use std::sync::OnceLock;
 
+use regex::Regex;
+
+use crate::config;
 use crate::days;
 use crate::err;
 
 #[derive(Debug)]
 pub struct Day{}
-impl days::Day for Day {
-    fn run(&self) {
-        println!("running day 4");
-        let mut file = match File::open("data/04/input.txt") {
-            Ok(file) => file,
-            Err(e) => panic!(e),
-        };
+impl Day {
+    fn read_file(&self, path: &str) -> Result<String, std::io::Error> {
+        let mut file = File::open(path)?;
         let mut contents = String::new();
-        match file.read_to_string(&mut contents) {
-            Err(e) => panic!(e),
-            _ => {}
+        file.read_to_string(&mut contents)?;
+        Ok(contents)
+    }
+
+    fn validate(&self, policy: &Policy, mode: ParseMode) {
+        let contents = match self.read_file("data/04/input.txt") {
+            Ok(contents) => contents,
+            Err(e) => panic!("{:?}", e),
         };
 
-        let passports = match parse_passports(&contents) {
+        let passports = match parse_passports(&contents, mode) {
             Ok(p) => p,
             Err(e) => {
                 println!("could not parse passports: {:?}", e);
@@ -29,41 +35,244 @@ impl days::Day for Day {
 
         println!(
             "valid passports: {}",
-            passports.iter().filter(|p| p.is_valid()).count()
+            passports.iter().filter(|p| policy.is_valid(p)).count()
         );
     }
+
+    fn report(&self, policy: &Policy, mode: ParseMode) {
+        let contents = match self.read_file("data/04/input.txt") {
+            Ok(contents) => contents,
+            Err(e) => panic!("{:?}", e),
+        };
+
+        print!("{}", ValidationReport::validate(&contents, policy, mode));
+    }
+
+    // Looks up a builtin policy by name, falling back to reading it from a
+    // file. Defaults to the strict policy.
+    fn load_policy(&self, name: Option<&String>) -> Option<Policy> {
+        let name = match name {
+            Some(name) => name,
+            None => return Policy::builtin("strict"),
+        };
+        if let Some(policy) = Policy::builtin(name) {
+            return Some(policy);
+        }
+
+        match self.read_file(name) {
+            Ok(contents) => match Policy::parse(&contents) {
+                Ok(policy) => Some(policy),
+                Err(e) => {
+                    println!("could not parse policy: {}", e);
+                    None
+                }
+            },
+            Err(e) => {
+                println!("could not read policy {}: {}", name, e);
+                None
+            }
+        }
+    }
+
+    // Reads a batch from the puzzle format, or from CSV if the file name
+    // ends in .csv. Fields that can't be parsed are kept as Invalid fields
+    // so nothing is lost.
+    fn load_batch(&self, path: &str) -> Option<Vec<Passport>> {
+        let contents = match self.read_file(path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                println!("could not read {}: {}", path, e);
+                return None;
+            }
+        };
+
+        if path.ends_with(".csv") {
+            match read_csv(&contents, ParseMode::Lenient) {
+                Ok(passports) => Some(passports),
+                Err(e) => {
+                    println!("could not parse {}: {}", path, e);
+                    None
+                }
+            }
+        } else {
+            let (passports, _) = parse_batch(&contents, ParseMode::Lenient);
+            Some(passports.into_iter().map(|p| p.passport).collect())
+        }
+    }
 }
 
-#[derive(PartialEq, Debug)]
+fn parse_mode(arg: Option<&String>) -> Option<ParseMode> {
+    match arg.map(|m| ParseMode::parse(m)) {
+        Some(Ok(mode)) => Some(mode),
+        Some(Err(e)) => {
+            println!("{}", e);
+            None
+        }
+        None => Some(ParseMode::Lenient),
+    }
+}
+
+impl days::Day for Day {
+    fn run(&self) {
+        println!("running day 4");
+        self.validate(Policy::strict(), ParseMode::Strict);
+    }
+
+    // Usage: 4 validate [presence|strict|policy_file] [lenient|strict]
+    //        4 report [presence|strict|policy_file] [lenient|strict]
+    //        4 export [puzzle|json|csv] [batch_file]
+    fn run_with_args(&self, args: &[String]) {
+        match args.first().map(|a| a.as_str()) {
+            Some("validate") => {
+                if let (Some(policy), Some(mode)) =
+                    (self.load_policy(args.get(1)), parse_mode(args.get(2)))
+                {
+                    self.validate(&policy, mode);
+                }
+            }
+            Some("report") => {
+                if let (Some(policy), Some(mode)) =
+                    (self.load_policy(args.get(1)), parse_mode(args.get(2)))
+                {
+                    self.report(&policy, mode);
+                }
+            }
+            Some("export") => {
+                let format = match args.get(1).map(|f| BatchFormat::parse(f)) {
+                    Some(Ok(format)) => format,
+                    Some(Err(e)) => {
+                        println!("could not parse format: {}", e);
+                        return;
+                    }
+                    None => BatchFormat::Json,
+                };
+                let path = args.get(2).map_or("data/04/input.txt", |p| p.as_str());
+                if let Some(passports) = self.load_batch(path) {
+                    print!("{}", write_batch(&passports, format));
+                }
+            }
+            Some(cmd) => println!("unknown command: {}", cmd),
+            None => self.run(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 enum Height {
-    In(u8),
-    Cm(u8),
+    Mm(f64),
+    Cm(f64),
+    M(f64),
+    In(f64),
+    FtIn(u32, f64),
 }
 
 impl Height {
     fn parse(s: &str) -> Result<Height, err::ParseError> {
         let clean_s = s.trim().to_lowercase();
-        if clean_s.len() < 3 {
-            return Err(err::ParseError::new("invalid height format", s));
+
+        // Feet and inches, written like 5'11" or just 6'.
+        if let Some((feet, inches)) = clean_s.split_once('\'') {
+            if feet.is_empty() || !feet.chars().all(|c| c.is_ascii_digit()) {
+                return Err(err::ParseError::new("invalid height size", s));
+            }
+            let feet = match feet.parse::<u32>() {
+                Ok(feet) => feet,
+                Err(_) => return Err(err::ParseError::new("invalid height size", s)),
+            };
+            let inches = match inches.strip_suffix('"') {
+                Some(inches) => Height::parse_size(inches, s)?,
+                None if inches.is_empty() => 0.0,
+                None => return Err(err::ParseError::new("invalid height format", s)),
+            };
+            if inches >= 12.0 {
+                return Err(err::ParseError::new("invalid height size", s));
+            }
+            return Ok(Height::FtIn(feet, inches));
         }
 
-        let num: String = clean_s
-            .chars()
-            .take_while(|c| *c >= '0' && *c <= '9')
-            .collect();
-        let unit: String = clean_s
-            .chars()
-            .skip_while(|c| *c >= '0' && *c <= '9')
-            .collect();
+        let split = clean_s
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(clean_s.len());
+        let (num, unit) = clean_s.split_at(split);
+        let size = Height::parse_size(num, s)?;
 
-        Ok(match num.parse::<u8>() {
-            Ok(size) => match unit.as_str() {
-                "cm" => Height::Cm(size),
-                "in" => Height::In(size),
-                _ => return Err(err::ParseError::new("invalid height format", s)),
-            },
-            Err(_) => return Err(err::ParseError::new("invalid height size", s)),
-        })
+        match Height::with_unit(unit, size) {
+            Some(height) => Ok(height),
+            None => Err(err::ParseError::new("invalid height format", s)),
+        }
+    }
+
+    // A non-negative decimal number, only digits and a single dot allowed.
+    fn parse_size(num: &str, s: &str) -> Result<f64, err::ParseError> {
+        if !num.chars().all(|c| c.is_ascii_digit() || c == '.') {
+            return Err(err::ParseError::new("invalid height size", s));
+        }
+        match num.parse::<f64>() {
+            Ok(size) => Ok(size),
+            Err(_) => Err(err::ParseError::new("invalid height size", s)),
+        }
+    }
+
+    // Builds a height in any of the single-number units.
+    fn with_unit(unit: &str, size: f64) -> Option<Height> {
+        match unit {
+            "mm" => Some(Height::Mm(size)),
+            "cm" => Some(Height::Cm(size)),
+            "m" => Some(Height::M(size)),
+            "in" => Some(Height::In(size)),
+            _ => None,
+        }
+    }
+
+    fn unit(&self) -> &'static str {
+        match self {
+            Height::Mm(_) => "mm",
+            Height::Cm(_) => "cm",
+            Height::M(_) => "m",
+            Height::In(_) => "in",
+            Height::FtIn(_, _) => "ftin",
+        }
+    }
+
+    // Centimeters are the canonical unit, heights in any other unit are
+    // converted to them before being compared.
+    fn to_cm(self) -> f64 {
+        match self {
+            Height::Mm(size) => size / 10.0,
+            Height::Cm(size) => size,
+            Height::M(size) => size * 100.0,
+            Height::In(size) => size * 2.54,
+            Height::FtIn(feet, inches) => (feet as f64 * 12.0 + inches) * 2.54,
+        }
+    }
+}
+
+impl PartialEq for Height {
+    fn eq(&self, other: &Height) -> bool {
+        self.partial_cmp(other) == Some(std::cmp::Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Height {
+    fn partial_cmp(&self, other: &Height) -> Option<std::cmp::Ordering> {
+        let (a, b) = (self.to_cm(), other.to_cm());
+        // Unit conversions leave small rounding errors behind, 1830mm and
+        // 1.83m should still be the same height.
+        if (a - b).abs() < 1e-9 {
+            return Some(std::cmp::Ordering::Equal);
+        }
+        a.partial_cmp(&b)
+    }
+}
+
+impl std::fmt::Display for Height {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Height::Mm(size) | Height::Cm(size) | Height::M(size) | Height::In(size) => {
+                write!(f, "{}{}", size, self.unit())
+            }
+            Height::FtIn(feet, inches) => write!(f, "{}'{}\"", feet, inches),
+        }
     }
 }
 
@@ -77,6 +286,21 @@ enum PassportField {
     EyeColor(String),
     PassportId(String),
     CountryId(String),
+    // A field that couldn't be parsed, kept around by lenient parsing so it
+    // shows up when the passport is validated.
+    Invalid {
+        key: String,
+        raw: String,
+        reason: String,
+    },
+}
+
+// Years are always written with exactly four digits.
+fn parse_year(s: &str) -> Option<u16> {
+    if s.len() != 4 || !s.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
 }
 
 impl PassportField {
@@ -90,16 +314,16 @@ impl PassportField {
         let field_value = parts[1].trim().to_owned();
 
         Ok(match field_key.to_lowercase().as_str() {
-            "byr" => match field_value.parse::<u16>() {
-                Ok(v) => PassportField::BirthYear(v),
+            "byr" => match parse_year(&field_value) {
+                Some(v) => PassportField::BirthYear(v),
                 _ => return Err(err::ParseError::new("invalid format", s)),
             },
-            "iyr" => match field_value.parse::<u16>() {
-                Ok(v) => PassportField::IssueYear(v),
+            "iyr" => match parse_year(&field_value) {
+                Some(v) => PassportField::IssueYear(v),
                 _ => return Err(err::ParseError::new("invalid format", s)),
             },
-            "eyr" => match field_value.parse::<u16>() {
-                Ok(v) => PassportField::ExpirationYear(v),
+            "eyr" => match parse_year(&field_value) {
+                Some(v) => PassportField::ExpirationYear(v),
                 _ => return Err(err::ParseError::new("invalid format", s)),
             },
             "hgt" => match Height::parse(&field_value) {
@@ -114,146 +338,924 @@ impl PassportField {
         })
     }
 
-    fn is_valid(&self) -> bool {
-        // Additional field validation
-        let hair_pattern = regex::Regex::new(r"#[a-f0-9]{6}").unwrap();
+    // Never fails, any field that doesn't parse comes back as Invalid along
+    // with the error explaining why.
+    fn parse_lenient(s: &str) -> (PassportField, Option<err::ParseError>) {
+        match PassportField::parse(s) {
+            Ok(field) => (field, None),
+            Err(e) => {
+                let (key, raw) = match s.find(':') {
+                    Some(idx) => (&s[..idx], &s[idx + 1..]),
+                    None => (s, ""),
+                };
+                let field = PassportField::Invalid {
+                    key: key.trim().to_lowercase(),
+                    raw: raw.trim().to_owned(),
+                    reason: e.msg().to_owned(),
+                };
+                (field, Some(e))
+            }
+        }
+    }
+
+    fn key(&self) -> &str {
+        match self {
+            PassportField::BirthYear(_) => "byr",
+            PassportField::IssueYear(_) => "iyr",
+            PassportField::ExpirationYear(_) => "eyr",
+            PassportField::Height(_) => "hgt",
+            PassportField::HairColor(_) => "hcl",
+            PassportField::EyeColor(_) => "ecl",
+            PassportField::PassportId(_) => "pid",
+            PassportField::CountryId(_) => "cid",
+            PassportField::Invalid { key, .. } => key,
+        }
+    }
+
+    fn year(&self) -> Option<u16> {
         match *self {
-            PassportField::BirthYear(y) if y < 1920 || y > 2002 => false,
-            PassportField::IssueYear(y) if y < 2010 || y > 2020 => false,
-            PassportField::ExpirationYear(y) if y < 2020 || y > 2030 => false,
-            PassportField::Height(Height::Cm(s)) if s < 150 || s > 193 => false,
-            PassportField::Height(Height::In(s)) if s < 59 || s > 76 => false,
-            PassportField::HairColor(ref c) if !hair_pattern.is_match(c) => false,
-            PassportField::EyeColor(ref c) => match c.as_str() {
-                "amb" | "blu" | "brn" | "gry" | "grn" | "hzl" | "oth" => true,
-                _ => return false,
+            PassportField::BirthYear(y)
+            | PassportField::IssueYear(y)
+            | PassportField::ExpirationYear(y) => Some(y),
+            _ => None,
+        }
+    }
+
+    fn height(&self) -> Option<&Height> {
+        match self {
+            PassportField::Height(h) => Some(h),
+            _ => None,
+        }
+    }
+
+    // The value as it would be written in a passport.
+    fn value(&self) -> String {
+        match self {
+            PassportField::BirthYear(y)
+            | PassportField::IssueYear(y)
+            | PassportField::ExpirationYear(y) => y.to_string(),
+            PassportField::Height(h) => h.to_string(),
+            PassportField::HairColor(v)
+            | PassportField::EyeColor(v)
+            | PassportField::PassportId(v)
+            | PassportField::CountryId(v) => v.clone(),
+            PassportField::Invalid { raw, .. } => raw.clone(),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum FieldType {
+    Year,
+    Height,
+    Text,
+    // Whatever the field holds, even if it couldn't be parsed.
+    Any,
+}
+
+impl FieldType {
+    fn parse(s: &str) -> Result<FieldType, err::ParseError> {
+        match s {
+            "year" => Ok(FieldType::Year),
+            "height" => Ok(FieldType::Height),
+            "text" => Ok(FieldType::Text),
+            "any" => Ok(FieldType::Any),
+            _ => Err(err::ParseError::new("unknown field type", s)),
+        }
+    }
+
+    fn matches(&self, field: &PassportField) -> bool {
+        match self {
+            FieldType::Year => field.year().is_some(),
+            FieldType::Height => field.height().is_some(),
+            FieldType::Text => !matches!(field, PassportField::Invalid { .. }),
+            FieldType::Any => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Constraint {
+    YearRange {
+        min: Option<i64>,
+        max: Option<i64>,
+    },
+    // Without a unit the range covers every height, otherwise it only
+    // applies to heights written in that unit.
+    HeightRange {
+        unit: Option<String>,
+        min: Option<Height>,
+        max: Option<Height>,
+    },
+    Pattern(Regex),
+    OneOf(Vec<String>),
+    Length(usize),
+    Numeric,
+    // Units a height is allowed to be written in.
+    Units(Vec<String>),
+}
+
+impl Constraint {
+    fn in_range<T: PartialOrd>(v: T, min: Option<T>, max: Option<T>) -> bool {
+        min.is_none_or(|min| v >= min) && max.is_none_or(|max| v <= max)
+    }
+
+    fn is_range(&self) -> bool {
+        matches!(
+            self,
+            Constraint::YearRange { .. } | Constraint::HeightRange { .. }
+        )
+    }
+
+    fn accepts(&self, field: &PassportField) -> bool {
+        match self {
+            Constraint::YearRange { min, max } => match field.year() {
+                Some(y) => Constraint::in_range(y as i64, *min, *max),
+                None => false,
+            },
+            Constraint::HeightRange { unit, min, max } => match (field.height(), unit) {
+                (Some(h), Some(unit)) if h.unit() != unit => true,
+                (Some(h), _) => Constraint::in_range(h, min.as_ref(), max.as_ref()),
+                (None, _) => false,
+            },
+            Constraint::Pattern(re) => re.is_match(&field.value()),
+            Constraint::OneOf(options) => options.contains(&field.value()),
+            Constraint::Length(len) => field.value().chars().count() == *len,
+            Constraint::Numeric => field.value().chars().all(|c| c.is_ascii_digit()),
+            Constraint::Units(units) => match field.height() {
+                Some(h) => units.iter().any(|u| u == h.unit()),
+                None => false,
             },
-            PassportField::PassportId(ref c) if c.len() != 9 || !c.parse::<u64>().is_ok() => false,
-            _ => true,
+        }
+    }
+
+    fn check(&self, field: &PassportField) -> Option<Violation> {
+        if self.accepts(field) {
+            None
+        } else if self.is_range() {
+            Some(Violation::OutOfRange(field.key().to_owned(), field.value()))
+        } else {
+            Some(Violation::BadFormat(field.key().to_owned(), field.value()))
         }
     }
 }
 
-#[derive(PartialEq, Debug)]
-struct Passport {
-    fields: Vec<PassportField>,
+// Why a passport failed a policy. Keys are the passport field keys.
+#[derive(PartialEq, Debug, Clone)]
+enum Violation {
+    Missing(String),
+    Duplicate(String, usize),
+    OutOfRange(String, String),
+    BadFormat(String, String),
+    // Key and the reason the field couldn't be parsed.
+    Invalid(String, String),
 }
 
-impl Passport {
-    fn parse(s: &str) -> Result<Passport, err::ParseError> {
-        let raw_fields: Vec<&str> = s
-            .split_whitespace()
-            .map(|f| f.trim())
-            .filter(|f| *f != "")
-            .collect();
+impl Violation {
+    fn reason(&self) -> &'static str {
+        match self {
+            Violation::Missing(_) => "missing field",
+            Violation::Duplicate(_, _) => "duplicate field",
+            Violation::OutOfRange(_, _) => "out of range",
+            Violation::BadFormat(_, _) => "bad format",
+            Violation::Invalid(_, _) => "invalid field",
+        }
+    }
+}
 
-        let mut fields = Vec::new();
-        for raw_field in raw_fields {
-            match PassportField::parse(raw_field) {
-                Ok(f) => fields.push(f),
-                Err(e) => return Err(e),
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::Missing(key) => write!(f, "{} {}", self.reason(), key),
+            Violation::Duplicate(key, count) => {
+                write!(f, "{} {} ({} times)", self.reason(), key, count)
+            }
+            Violation::OutOfRange(key, value)
+            | Violation::BadFormat(key, value)
+            | Violation::Invalid(key, value) => write!(f, "{} {}: {}", self.reason(), key, value),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct FieldRule {
+    key: String,
+    field_type: FieldType,
+    required: bool,
+    constraints: Vec<Constraint>,
+}
+
+impl FieldRule {
+    // Only the first broken constraint is reported for each field.
+    fn check(&self, field: &PassportField) -> Option<Violation> {
+        if let PassportField::Invalid { key, reason, .. } = field {
+            if self.field_type != FieldType::Any {
+                return Some(Violation::Invalid(key.clone(), reason.clone()));
+            }
+        }
+        if !self.field_type.matches(field) {
+            return Some(Violation::BadFormat(field.key().to_owned(), field.value()));
+        }
+        self.constraints.iter().find_map(|c| c.check(field))
+    }
+}
+
+// Which fields a passport needs and what they can hold, loaded from a
+// config file like the ones in data/04/policies.
+#[derive(Debug)]
+struct Policy {
+    rules: Vec<FieldRule>,
+}
+
+const FIELD_KEYS: [&str; 8] = ["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid", "cid"];
+
+impl Policy {
+    fn parse(s: &str) -> Result<Policy, err::ParseError> {
+        let mut rules = Vec::new();
+        for section in config::parse(s)? {
+            if !FIELD_KEYS.contains(&section.name.as_str()) {
+                return Err(
+                    err::ParseError::new("unknown field", &section.name).on_line(section.line)
+                );
+            }
+
+            let field_type = match section.get("type").and_then(|v| v.as_str()) {
+                Some(t) => FieldType::parse(t).map_err(|e| e.on_line(section.line))?,
+                None => {
+                    return Err(err::ParseError::new("missing field type", &section.name)
+                        .on_line(section.line))
+                }
+            };
+
+            let mut rule = FieldRule {
+                key: section.name.clone(),
+                field_type,
+                required: false,
+                constraints: Vec::new(),
+            };
+            let mut year_range = (None, None);
+            let mut height_ranges: Vec<(Option<String>, Option<Height>, Option<Height>)> =
+                Vec::new();
+            let mut has_units = false;
+
+            for (setting, value, line) in &section.settings {
+                let bad_value =
+                    || err::ParseError::new("invalid value for", setting).on_line(*line);
+                let setting = setting.as_str();
+                match (setting, field_type) {
+                    ("type", _) => {}
+                    ("required", _) => rule.required = value.as_bool().ok_or_else(bad_value)?,
+                    ("min", FieldType::Year) => {
+                        year_range.0 = Some(value.as_int().ok_or_else(bad_value)?)
+                    }
+                    ("max", FieldType::Year) => {
+                        year_range.1 = Some(value.as_int().ok_or_else(bad_value)?)
+                    }
+                    // Plain min and max are in centimeters and cover every
+                    // height, min_<unit> and max_<unit> only cover that unit.
+                    (_, FieldType::Height)
+                        if setting.starts_with("min") || setting.starts_with("max") =>
+                    {
+                        let unit = match &setting[3..] {
+                            "" => None,
+                            suffix => match suffix.strip_prefix('_') {
+                                Some(unit) => Some(unit),
+                                None => {
+                                    return Err(err::ParseError::new(
+                                        "unknown setting for field type",
+                                        setting,
+                                    )
+                                    .on_line(*line))
+                                }
+                            },
+                        };
+                        let size = value.as_int().ok_or_else(bad_value)? as f64;
+                        let bound = match Height::with_unit(unit.unwrap_or("cm"), size) {
+                            Some(bound) => bound,
+                            None => {
+                                return Err(err::ParseError::new("unknown height unit", setting)
+                                    .on_line(*line))
+                            }
+                        };
+                        let unit = unit.map(|u| u.to_owned());
+                        let idx = match height_ranges.iter().position(|(u, _, _)| *u == unit) {
+                            Some(idx) => idx,
+                            None => {
+                                height_ranges.push((unit, None, None));
+                                height_ranges.len() - 1
+                            }
+                        };
+                        if setting.starts_with("min") {
+                            height_ranges[idx].1 = Some(bound);
+                        } else {
+                            height_ranges[idx].2 = Some(bound);
+                        }
+                    }
+                    ("pattern", _) => {
+                        // Patterns have to match the whole value, not just part of it.
+                        let pattern = value.as_str().ok_or_else(bad_value)?;
+                        match Regex::new(&format!("^(?:{})$", pattern)) {
+                            Ok(re) => rule.constraints.push(Constraint::Pattern(re)),
+                            Err(_) => return Err(bad_value()),
+                        }
+                    }
+                    ("units", FieldType::Height) => {
+                        let mut units = Vec::new();
+                        for unit in value.as_list().ok_or_else(bad_value)? {
+                            let unit = unit.as_str().ok_or_else(bad_value)?;
+                            if unit != "ftin" && Height::with_unit(unit, 0.0).is_none() {
+                                return Err(err::ParseError::new("unknown height unit", unit)
+                                    .on_line(*line));
+                            }
+                            units.push(unit.to_owned());
+                        }
+                        rule.constraints.push(Constraint::Units(units));
+                        has_units = true;
+                    }
+                    ("one_of", _) => {
+                        let mut options = Vec::new();
+                        for option in value.as_list().ok_or_else(bad_value)? {
+                            options.push(option.as_str().ok_or_else(bad_value)?.to_owned());
+                        }
+                        rule.constraints.push(Constraint::OneOf(options));
+                    }
+                    ("length", _) => match value.as_int() {
+                        Some(len) if len >= 0 => {
+                            rule.constraints.push(Constraint::Length(len as usize))
+                        }
+                        _ => return Err(bad_value()),
+                    },
+                    ("numeric", _) => {
+                        if value.as_bool().ok_or_else(bad_value)? {
+                            rule.constraints.push(Constraint::Numeric);
+                        }
+                    }
+                    _ => {
+                        return Err(
+                            err::ParseError::new("unknown setting for field type", setting)
+                                .on_line(*line),
+                        )
+                    }
+                }
+            }
+
+            if year_range != (None, None) {
+                rule.constraints.push(Constraint::YearRange {
+                    min: year_range.0,
+                    max: year_range.1,
+                });
+            }
+            // With only per-unit ranges, a height in some other unit has
+            // nothing bounding it, so it's only allowed if listed in units.
+            if !has_units && height_ranges.iter().all(|(unit, _, _)| unit.is_some()) {
+                let units: Vec<String> = height_ranges.iter().flat_map(|r| r.0.clone()).collect();
+                if !units.is_empty() {
+                    rule.constraints.push(Constraint::Units(units));
+                }
+            }
+            for (unit, min, max) in height_ranges {
+                rule.constraints
+                    .push(Constraint::HeightRange { unit, min, max });
             }
+
+            rules.push(rule);
         }
 
-        Ok(Passport { fields: fields })
+        Ok(Policy { rules })
+    }
+
+    fn builtin(name: &str) -> Option<Policy> {
+        let source = match name {
+            "presence" => include_str!("../data/04/policies/presence.toml"),
+            "strict" => include_str!("../data/04/policies/strict.toml"),
+            _ => return None,
+        };
+
+        match Policy::parse(source) {
+            Ok(policy) => Some(policy),
+            Err(e) => panic!("invalid builtin policy {}: {}", name, e),
+        }
     }
 
-    fn is_valid(&self) -> bool {
-        // TODO I don't like how this is handled but I don't have a better idea right now.
-        let required_fields = vec![
-            PassportField::BirthYear(0),
-            PassportField::IssueYear(0),
-            PassportField::ExpirationYear(0),
-            PassportField::Height(Height::In(0)),
-            PassportField::HairColor(String::new()),
-            PassportField::EyeColor(String::new()),
-            PassportField::PassportId(String::new()),
-        ];
+    // The part 2 policy, built once and shared.
+    fn strict() -> &'static Policy {
+        static STRICT: OnceLock<Policy> = OnceLock::new();
+        STRICT.get_or_init(|| Policy::builtin("strict").unwrap())
+    }
 
-        for req_field in required_fields {
-            let fields: Vec<&PassportField> = self
+    fn check(&self, passport: &Passport) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for rule in &self.rules {
+            let fields: Vec<&PassportField> = passport
                 .fields
                 .iter()
-                .filter(|f| std::mem::discriminant(*f) == std::mem::discriminant(&req_field))
-                .map(|f| f)
+                .filter(|f| f.key() == rule.key)
                 .collect();
 
-            if fields.len() != 1 || !fields[0].is_valid() {
-                return false;
+            match fields.len() {
+                0 if rule.required => violations.push(Violation::Missing(rule.key.clone())),
+                0 => {}
+                1 => violations.extend(rule.check(fields[0])),
+                n => violations.push(Violation::Duplicate(rule.key.clone(), n)),
+            }
+        }
+
+        // Invalid fields the rules above didn't see still count against the passport.
+        for field in &passport.fields {
+            if let PassportField::Invalid { key, reason, .. } = field {
+                if !self.rules.iter().any(|r| &r.key == key) {
+                    violations.push(Violation::Invalid(key.clone(), reason.clone()));
+                }
             }
         }
+        violations
+    }
 
-        true
+    fn is_valid(&self, passport: &Passport) -> bool {
+        self.check(passport).is_empty()
     }
 }
 
-fn parse_passports(s: &str) -> Result<Vec<Passport>, err::ParseError> {
-    let raw_passports: Vec<&str> = s
-        .split("\n\n")
-        .map(|p| p.trim())
-        .filter(|p| *p != "")
-        .collect();
+impl std::fmt::Display for PassportField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.key(), self.value())
+    }
+}
 
-    let mut passports = Vec::new();
-    for raw_passport in raw_passports {
-        match Passport::parse(raw_passport) {
-            Ok(p) => passports.push(p),
-            // Throw out any passports that can't be parsed because they won't
-            // be valid for our purposes anyway.
-            Err(_) => {}
+#[derive(PartialEq, Debug)]
+struct Passport {
+    fields: Vec<PassportField>,
+}
+
+// Written back out in the puzzle format, all on one line.
+impl std::fmt::Display for Passport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields: Vec<String> = self.fields.iter().map(|f| f.to_string()).collect();
+        write!(f, "{}", fields.join(" "))
+    }
+}
+
+impl Passport {
+    // Parses every field it can, keeping the rest as Invalid fields. The
+    // errors for those are returned too, positioned relative to first_line.
+    fn parse_lenient(s: &str, first_line: usize) -> (Passport, Vec<err::ParseError>) {
+        let mut fields = Vec::new();
+        let mut diagnostics = Vec::new();
+        for (idx, line) in s.split('\n').enumerate() {
+            for raw_field in line.split_whitespace() {
+                let (field, e) = PassportField::parse_lenient(raw_field);
+                fields.push(field);
+                diagnostics.extend(e.map(|e| e.on_line(first_line + idx)));
+            }
         }
+
+        (Passport { fields }, diagnostics)
     }
+}
 
-    Ok(passports)
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum ParseMode {
+    // Passports with fields that can't be parsed are dropped.
+    Strict,
+    // Unparsable fields are kept on the passport as Invalid fields.
+    Lenient,
 }
 
-#[cfg(test)]
-mod tests {
-    macro_rules! validate_tests {
-        ($($name:ident: $expected:tt, $value:expr,)*) => {
-                $(
-                    #[test]
-                    fn $name() {
-                        assert_eq!($value.is_valid(), $expected);
-                    }
-                )*
-        };
+impl ParseMode {
+    fn parse(s: &str) -> Result<ParseMode, err::ParseError> {
+        match s {
+            "strict" => Ok(ParseMode::Strict),
+            "lenient" => Ok(ParseMode::Lenient),
+            _ => Err(err::ParseError::new("unknown parse mode", s)),
+        }
     }
+}
 
-    mod height {
-        use super::super::*;
+#[derive(PartialEq, Debug)]
+struct ParsedPassport {
+    // 1-based position of the passport in its batch.
+    index: usize,
+    line: usize,
+    passport: Passport,
+}
 
-        #[test]
-        fn parse_valid_inches() {
-            assert_eq!(Height::parse("230in"), Ok(Height::In(230)),)
-        }
-        #[test]
-        fn parse_valid_centimeters() {
-            assert_eq!(Height::parse("230cm"), Ok(Height::Cm(230)),)
+// Parses a whole batch, returning the passports that were kept along with
+// diagnostics for every field that couldn't be parsed.
+fn parse_batch(s: &str, mode: ParseMode) -> (Vec<ParsedPassport>, Vec<err::ParseError>) {
+    let mut passports = Vec::new();
+    let mut diagnostics = Vec::new();
+    for (idx, (line, raw_passport)) in split_passports(s).into_iter().enumerate() {
+        let (passport, errors) = Passport::parse_lenient(raw_passport, line);
+        let keep = mode == ParseMode::Lenient || errors.is_empty();
+        diagnostics.extend(errors);
+        if keep {
+            passports.push(ParsedPassport {
+                index: idx + 1,
+                line,
+                passport,
+            });
         }
     }
 
-    mod passportfield {
-        use super::super::*;
+    (passports, diagnostics)
+}
 
-        #[test]
-        fn parse_valid_birth_year() {
-            assert_eq!(
-                PassportField::parse("byr:1937"),
-                Ok(PassportField::BirthYear(1937)),
-            )
+// Splits a batch on blank lines, returning the text of each passport along
+// with the line it starts on.
+fn split_passports(s: &str) -> Vec<(usize, &str)> {
+    let mut passports = Vec::new();
+    let mut start: Option<(usize, usize)> = None;
+    let mut offset = 0;
+    for (idx, line) in s.split('\n').enumerate() {
+        if line.trim().is_empty() {
+            if let Some((line_num, begin)) = start.take() {
+                passports.push((line_num, s[begin..offset].trim()));
+            }
+        } else if start.is_none() {
+            start = Some((idx + 1, offset));
         }
-        #[test]
-        fn parse_valid_issue_year() {
-            assert_eq!(
-                PassportField::parse("iyr:1937"),
-                Ok(PassportField::IssueYear(1937)),
-            )
+        offset += line.len() + 1;
+    }
+    if let Some((line_num, begin)) = start {
+        passports.push((line_num, s[begin..].trim()));
+    }
+
+    passports
+}
+
+fn parse_passports(s: &str, mode: ParseMode) -> Result<Vec<Passport>, err::ParseError> {
+    // In strict mode, passports that can't be parsed are thrown out because
+    // they won't be valid for our purposes anyway.
+    let (passports, _) = parse_batch(s, mode);
+    Ok(passports.into_iter().map(|p| p.passport).collect())
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum BatchFormat {
+    Puzzle,
+    Json,
+    Csv,
+}
+
+impl BatchFormat {
+    fn parse(s: &str) -> Result<BatchFormat, err::ParseError> {
+        match s.trim().to_lowercase().as_str() {
+            "puzzle" => Ok(BatchFormat::Puzzle),
+            "json" => Ok(BatchFormat::Json),
+            "csv" => Ok(BatchFormat::Csv),
+            _ => Err(err::ParseError::new("unknown batch format", s)),
         }
-        #[test]
-        fn parse_valid_expiration_year() {
-            assert_eq!(
-                PassportField::parse("eyr:1937"),
+    }
+}
+
+fn write_batch(passports: &[Passport], format: BatchFormat) -> String {
+    match format {
+        BatchFormat::Puzzle => write_puzzle(passports),
+        BatchFormat::Json => write_json(passports),
+        BatchFormat::Csv => write_csv(passports),
+    }
+}
+
+// One passport per line with a blank line between them, which
+// parse_passports reads back in.
+fn write_puzzle(passports: &[Passport]) -> String {
+    let lines: Vec<String> = passports.iter().map(|p| p.to_string()).collect();
+    lines.join("\n\n") + "\n"
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            c if c.is_control() => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// An array with an object per passport. Values are kept as the strings
+// they were written as, and fields show up in their original order.
+fn write_json(passports: &[Passport]) -> String {
+    let objects: Vec<String> = passports
+        .iter()
+        .map(|p| {
+            let fields: Vec<String> = p
+                .fields
+                .iter()
+                .map(|f| format!("{}:{}", json_string(f.key()), json_string(&f.value())))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        })
+        .collect();
+
+    format!("[{}]\n", objects.join(","))
+}
+
+// The known keys in their usual order, followed by any unknown keys from
+// Invalid fields in the order they first show up.
+fn batch_keys(passports: &[Passport]) -> Vec<String> {
+    let mut keys: Vec<String> = FIELD_KEYS
+        .iter()
+        .filter(|k| {
+            passports
+                .iter()
+                .any(|p| p.fields.iter().any(|f| f.key() == **k))
+        })
+        .map(|k| k.to_string())
+        .collect();
+    for field in passports.iter().flat_map(|p| &p.fields) {
+        if !FIELD_KEYS.contains(&field.key()) && !keys.iter().any(|k| k == field.key()) {
+            keys.push(field.key().to_owned());
+        }
+    }
+    keys
+}
+
+fn csv_cell(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+// A column per field key. Values never contain whitespace, so a key that
+// shows up more than once is written as its values separated by spaces.
+fn write_csv(passports: &[Passport]) -> String {
+    let keys = batch_keys(passports);
+    let header: Vec<String> = keys.iter().map(|key| csv_cell(key)).collect();
+    let mut out = header.join(",") + "\n";
+    for passport in passports {
+        let cells: Vec<String> = keys
+            .iter()
+            .map(|key| {
+                let values: Vec<String> = passport
+                    .fields
+                    .iter()
+                    .filter(|f| f.key() == key)
+                    .map(|f| f.value())
+                    .collect();
+                csv_cell(&values.join(" "))
+            })
+            .collect();
+        out += &cells.join(",");
+        out += "\n";
+    }
+    out
+}
+
+fn parse_csv_line(line: &str) -> Result<Vec<String>, err::ParseError> {
+    let mut cells = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        let mut cell = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        cell.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => cell.push(c),
+                    None => return Err(err::ParseError::new("unterminated quote", line)),
+                }
+            }
+            if !matches!(chars.peek(), None | Some(',')) {
+                return Err(err::ParseError::new("unexpected text after quote", line));
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                cell.push(c);
+            }
+        }
+        cells.push(cell);
+
+        if chars.next().is_none() {
+            return Ok(cells);
+        }
+    }
+}
+
+// Reads the format write_csv produces. Empty cells are missing fields.
+// Fields that don't parse are an error in strict mode and kept as Invalid
+// fields in lenient mode.
+fn read_csv(s: &str, mode: ParseMode) -> Result<Vec<Passport>, err::ParseError> {
+    let mut lines = s
+        .split('\n')
+        .enumerate()
+        .map(|(idx, l)| (idx + 1, l.trim_end_matches('\r')))
+        .filter(|(_, l)| !l.trim().is_empty());
+
+    let keys = match lines.next() {
+        Some((line_num, header)) => parse_csv_line(header).map_err(|e| e.on_line(line_num))?,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut passports = Vec::new();
+    for (line_num, line) in lines {
+        let cells = parse_csv_line(line).map_err(|e| e.on_line(line_num))?;
+        if cells.len() != keys.len() {
+            return Err(err::ParseError::new("wrong number of cells", line).on_line(line_num));
+        }
+
+        let mut fields = Vec::new();
+        for (key, cell) in keys.iter().zip(cells.iter()) {
+            for value in cell.split_whitespace() {
+                let raw = format!("{}:{}", key.trim(), value);
+                match (PassportField::parse_lenient(&raw), mode) {
+                    ((_, Some(e)), ParseMode::Strict) => return Err(e.on_line(line_num)),
+                    ((field, _), _) => fields.push(field),
+                }
+            }
+        }
+        passports.push(Passport { fields });
+    }
+
+    Ok(passports)
+}
+
+#[derive(PartialEq, Debug)]
+struct PassportResult {
+    // 1-based position of the passport in its batch.
+    index: usize,
+    line: usize,
+    violations: Vec<Violation>,
+}
+
+#[derive(PartialEq, Debug)]
+struct ValidationReport {
+    results: Vec<PassportResult>,
+    diagnostics: Vec<err::ParseError>,
+}
+
+impl ValidationReport {
+    fn validate(s: &str, policy: &Policy, mode: ParseMode) -> ValidationReport {
+        let (passports, diagnostics) = parse_batch(s, mode);
+        let results = passports
+            .into_iter()
+            .map(|p| PassportResult {
+                index: p.index,
+                line: p.line,
+                violations: policy.check(&p.passport),
+            })
+            .collect();
+
+        ValidationReport {
+            results,
+            diagnostics,
+        }
+    }
+
+    fn valid(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|r| r.violations.is_empty())
+            .count()
+    }
+
+    // How many times each kind of violation shows up across the batch.
+    fn histogram(&self) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+        for violation in self.results.iter().flat_map(|r| &r.violations) {
+            *counts.entry(violation.reason()).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+impl std::fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for diagnostic in &self.diagnostics {
+            writeln!(f, "parse error {}", diagnostic)?;
+        }
+        for result in self.results.iter().filter(|r| !r.violations.is_empty()) {
+            let violations: Vec<String> = result.violations.iter().map(|v| v.to_string()).collect();
+            writeln!(
+                f,
+                "passport {} (line {}): {}",
+                result.index,
+                result.line,
+                violations.join(", ")
+            )?;
+        }
+
+        writeln!(
+            f,
+            "valid passports: {} of {}",
+            self.valid(),
+            self.results.len()
+        )?;
+        for (reason, count) in self.histogram() {
+            writeln!(f, "{}: {}", reason, count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Whether the strict policy has anything against the field, other than
+    // the rest of the passport missing.
+    fn strict_accepts(field: PassportField) -> bool {
+        let passport = Passport {
+            fields: vec![field],
+        };
+        Policy::strict()
+            .check(&passport)
+            .iter()
+            .all(|v| matches!(v, Violation::Missing(_)))
+    }
+
+    macro_rules! validate_tests {
+        ($($name:ident: $expected:tt, $value:expr,)*) => {
+                $(
+                    #[test]
+                    fn $name() {
+                        assert_eq!(super::strict_accepts($value), $expected);
+                    }
+                )*
+        };
+    }
+
+    mod height {
+        use super::super::*;
+
+        #[test]
+        fn parse_valid_inches() {
+            assert_eq!(Height::parse("230in"), Ok(Height::In(230.0)),)
+        }
+        #[test]
+        fn parse_valid_centimeters() {
+            assert_eq!(Height::parse("230cm"), Ok(Height::Cm(230.0)),)
+        }
+        #[test]
+        fn parse_other_units() {
+            assert_eq!(Height::parse("1830mm"), Ok(Height::Mm(1830.0)));
+            assert_eq!(Height::parse("1.83m"), Ok(Height::M(1.83)));
+            assert_eq!(Height::parse("172.5CM"), Ok(Height::Cm(172.5)));
+            assert_eq!(Height::parse("5'11\""), Ok(Height::FtIn(5, 11.0)));
+            assert_eq!(Height::parse("6'"), Ok(Height::FtIn(6, 0.0)));
+            assert_eq!(Height::parse("5'7.5\""), Ok(Height::FtIn(5, 7.5)));
+            assert_eq!(
+                Height::parse("5'13\""),
+                Err(err::ParseError::new("invalid height size", "5'13\""))
+            );
+        }
+        #[test]
+        fn parse_errors() {
+            for bad in &[
+                "190", "190ft", "cm", "1.8.3m", "-5cm", "5'11", "x'2\"", "'2\"",
+            ] {
+                assert!(Height::parse(bad).is_err(), "{} should not parse", bad);
+            }
+        }
+        #[test]
+        fn keeps_unit() {
+            for raw in &["1830mm", "183cm", "1.83m", "72in", "6'0\"", "5'7.5\""] {
+                let height = Height::parse(raw).unwrap();
+                assert_eq!(height.to_string(), *raw);
+            }
+        }
+        #[test]
+        fn compare_across_units() {
+            assert_eq!(Height::Mm(1830.0), Height::M(1.83));
+            assert_eq!(Height::In(72.0), Height::FtIn(6, 0.0));
+            assert_eq!(Height::In(1.0), Height::Cm(2.54));
+            assert!(Height::In(59.0) < Height::Cm(150.0));
+            assert!(Height::FtIn(6, 4.0) > Height::M(1.9));
+            assert!((Height::FtIn(5, 11.0).to_cm() - 180.34).abs() < 1e-9);
+        }
+    }
+
+    mod passportfield {
+        use super::super::*;
+
+        #[test]
+        fn parse_valid_birth_year() {
+            assert_eq!(
+                PassportField::parse("byr:1937"),
+                Ok(PassportField::BirthYear(1937)),
+            )
+        }
+        #[test]
+        fn parse_valid_issue_year() {
+            assert_eq!(
+                PassportField::parse("iyr:1937"),
+                Ok(PassportField::IssueYear(1937)),
+            )
+        }
+        #[test]
+        fn parse_valid_expiration_year() {
+            assert_eq!(
+                PassportField::parse("eyr:1937"),
                 Ok(PassportField::ExpirationYear(1937)),
             )
         }
@@ -261,7 +1263,7 @@ mod tests {
         fn parse_valid_height() {
             assert_eq!(
                 PassportField::parse("hgt:183cm"),
-                Ok(PassportField::Height(Height::Cm(183))),
+                Ok(PassportField::Height(Height::Cm(183.0))),
             )
         }
         #[test]
@@ -315,18 +1317,18 @@ mod tests {
             expiration_year_above:  false, PassportField::ExpirationYear(2031),
         }
         validate_tests! {
-            height_cm_below:  false, PassportField::Height(Height::Cm(149)),
-            height_cm_bottom: true,  PassportField::Height(Height::Cm(150)),
-            height_cm_mid:    true,  PassportField::Height(Height::Cm(160)),
-            height_cm_top:    true,  PassportField::Height(Height::Cm(193)),
-            height_cm_above:  false, PassportField::Height(Height::Cm(194)),
+            height_cm_below:  false, PassportField::Height(Height::Cm(149.0)),
+            height_cm_bottom: true,  PassportField::Height(Height::Cm(150.0)),
+            height_cm_mid:    true,  PassportField::Height(Height::Cm(160.0)),
+            height_cm_top:    true,  PassportField::Height(Height::Cm(193.0)),
+            height_cm_above:  false, PassportField::Height(Height::Cm(194.0)),
         }
         validate_tests! {
-            height_in_below:  false, PassportField::Height(Height::In(58)),
-            height_in_bottom: true,  PassportField::Height(Height::In(59)),
-            height_in_mid:    true,  PassportField::Height(Height::In(65)),
-            height_in_top:    true,  PassportField::Height(Height::In(76)),
-            height_in_above:  false, PassportField::Height(Height::In(77)),
+            height_in_below:  false, PassportField::Height(Height::In(58.0)),
+            height_in_bottom: true,  PassportField::Height(Height::In(59.0)),
+            height_in_mid:    true,  PassportField::Height(Height::In(65.0)),
+            height_in_top:    true,  PassportField::Height(Height::In(76.0)),
+            height_in_above:  false, PassportField::Height(Height::In(77.0)),
         }
         validate_tests! {
             hair_color_0: true, PassportField::HairColor(String::from("#000000")),
@@ -372,6 +1374,95 @@ mod tests {
             passport_id_a:           false, PassportField::PassportId(String::from("aaaaaaaaa")),
             passport_id_too_short:   false, PassportField::PassportId(String::from("12345")),
             passport_id_alpha_start: false, PassportField::PassportId(String::from("a12345678")),
+            passport_id_plus_sign:   false, PassportField::PassportId(String::from("+12345678")),
+            passport_id_too_long:    false, PassportField::PassportId(String::from("0123456789")),
+        }
+    }
+
+    // The examples given in the puzzle description, run through the parser
+    // and the strict policy the same way passports from a batch are.
+    mod examples {
+        use super::super::*;
+
+        macro_rules! example_tests {
+            ($($name:ident: $expected:tt, $raw:expr,)*) => {
+                $(
+                    #[test]
+                    fn $name() {
+                        let (field, _) = PassportField::parse_lenient($raw);
+                        assert_eq!(super::strict_accepts(field), $expected);
+                    }
+                )*
+            };
+        }
+
+        example_tests! {
+            byr_valid:          true,  "byr:2002",
+            byr_too_late:       false, "byr:2003",
+            byr_too_early:      false, "byr:1919",
+            byr_five_digits:    false, "byr:02002",
+            byr_sign:           false, "byr:+200",
+            iyr_valid:          true,  "iyr:2010",
+            iyr_too_late:       false, "iyr:2021",
+            eyr_valid:          true,  "eyr:2030",
+            eyr_too_early:      false, "eyr:2019",
+            hgt_valid_in:       true,  "hgt:60in",
+            hgt_valid_cm:       true,  "hgt:190cm",
+            hgt_too_tall_in:    false, "hgt:190in",
+            hgt_no_unit:        false, "hgt:190",
+            hcl_valid:          true,  "hcl:#123abc",
+            hcl_bad_char:       false, "hcl:#123abz",
+            hcl_no_hash:        false, "hcl:123abc",
+            hcl_trailing:       false, "hcl:#123abcz",
+            hcl_leading:        false, "hcl:z#123abc",
+            hcl_upper:          false, "hcl:#123ABC",
+            ecl_valid:          true,  "ecl:brn",
+            ecl_invalid:        false, "ecl:wat",
+            ecl_prefix:         false, "ecl:brnn",
+            pid_valid:          true,  "pid:000000001",
+            pid_too_long:       false, "pid:0123456789",
+            cid_anything:       true,  "cid:whatever",
+        }
+
+        #[test]
+        fn invalid_passports() {
+            let batch = "eyr:1972 cid:100
+hcl:#18171d ecl:amb hgt:170 pid:186cm iyr:2018 byr:1926
+
+iyr:2019
+hcl:#602927 eyr:1967 hgt:170cm
+ecl:grn pid:012533040 byr:1946
+
+hcl:dab227 iyr:2012
+ecl:brn hgt:182cm pid:021572410 eyr:2020 byr:1992 cid:277
+
+hgt:59cm ecl:zzz
+eyr:2038 hcl:74454a iyr:2023
+pid:3556412378 byr:2007
+";
+            let report = ValidationReport::validate(batch, Policy::strict(), ParseMode::Lenient);
+            assert_eq!(report.results.len(), 4);
+            assert_eq!(report.valid(), 0);
+        }
+
+        #[test]
+        fn valid_passports() {
+            let batch = "pid:087499704 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980
+hcl:#623a2f
+
+eyr:2029 ecl:blu cid:129 byr:1989
+iyr:2014 pid:896056539 hcl:#a97842 hgt:165cm
+
+hcl:#888785
+hgt:164cm byr:2001 iyr:2015 cid:88
+pid:545766238 ecl:hzl
+eyr:2022
+
+iyr:2010 hgt:158cm hcl:#b6652a ecl:blu byr:1944 eyr:2021 pid:093154719
+";
+            let report = ValidationReport::validate(batch, Policy::strict(), ParseMode::Lenient);
+            assert_eq!(report.results.len(), 4);
+            assert_eq!(report.valid(), 4);
         }
     }
 
@@ -381,24 +1472,28 @@ mod tests {
         #[test]
         fn parse_valid_syntax() {
             assert_eq!(
-                Passport::parse(
+                Passport::parse_lenient(
                     r"
                     ecl:gry pid:860033327 eyr:2020 hcl:#fffffd
                     byr:1937 iyr:2017 cid:147 hgt:183cm  
-                    "
+                    ",
+                    1,
                 ),
-                Ok(Passport {
-                    fields: vec![
-                        PassportField::EyeColor(String::from("gry")),
-                        PassportField::PassportId(String::from("860033327")),
-                        PassportField::ExpirationYear(2020),
-                        PassportField::HairColor(String::from("#fffffd")),
-                        PassportField::BirthYear(1937),
-                        PassportField::IssueYear(2017),
-                        PassportField::CountryId(String::from("147")),
-                        PassportField::Height(Height::Cm(183)),
-                    ],
-                })
+                (
+                    Passport {
+                        fields: vec![
+                            PassportField::EyeColor(String::from("gry")),
+                            PassportField::PassportId(String::from("860033327")),
+                            PassportField::ExpirationYear(2020),
+                            PassportField::HairColor(String::from("#fffffd")),
+                            PassportField::BirthYear(1937),
+                            PassportField::IssueYear(2017),
+                            PassportField::CountryId(String::from("147")),
+                            PassportField::Height(Height::Cm(183.0)),
+                        ],
+                    },
+                    vec![],
+                )
             )
         }
 
@@ -420,7 +1515,8 @@ mod tests {
 
                     hcl:#cfa07d eyr:2025 pid:166559648
                     iyr:2011 ecl:brn hgt:59in
-                    "
+                    ",
+                    ParseMode::Strict,
                 ),
                 Ok(vec![
                     Passport {
@@ -432,7 +1528,7 @@ mod tests {
                             PassportField::BirthYear(1937),
                             PassportField::IssueYear(2017),
                             PassportField::CountryId(String::from("147")),
-                            PassportField::Height(Height::Cm(183)),
+                            PassportField::Height(Height::Cm(183.0)),
                         ],
                     },
                     Passport {
@@ -454,7 +1550,7 @@ mod tests {
                             PassportField::EyeColor(String::from("brn")),
                             PassportField::PassportId(String::from("760753108")),
                             PassportField::BirthYear(1931),
-                            PassportField::Height(Height::Cm(179)),
+                            PassportField::Height(Height::Cm(179.0)),
                         ],
                     },
                     Passport {
@@ -464,7 +1560,7 @@ mod tests {
                             PassportField::PassportId(String::from("166559648")),
                             PassportField::IssueYear(2011),
                             PassportField::EyeColor(String::from("brn")),
-                            PassportField::Height(Height::In(59)),
+                            PassportField::Height(Height::In(59.0)),
                         ],
                     },
                 ]),
@@ -473,77 +1569,880 @@ mod tests {
 
         #[test]
         fn all_fields_is_valid() {
+            assert!(Policy::strict().is_valid(&Passport {
+                fields: vec![
+                    PassportField::EyeColor(String::from("gry")),
+                    PassportField::PassportId(String::from("860033327")),
+                    PassportField::ExpirationYear(2020),
+                    PassportField::HairColor(String::from("#fffffd")),
+                    PassportField::BirthYear(1937),
+                    PassportField::IssueYear(2017),
+                    PassportField::CountryId(String::from("147")),
+                    PassportField::Height(Height::Cm(183.0)),
+                ],
+            }));
+        }
+        #[test]
+        fn missing_field_is_not_valid() {
+            assert!(!Policy::strict().is_valid(&Passport {
+                fields: vec![
+                    PassportField::EyeColor(String::from("gry")),
+                    PassportField::PassportId(String::from("860033327")),
+                    PassportField::ExpirationYear(2020),
+                    PassportField::HairColor(String::from("#fffffd")),
+                    PassportField::BirthYear(1937),
+                    PassportField::IssueYear(2017),
+                    PassportField::CountryId(String::from("147")),
+                    // Height missing
+                ],
+            }));
+        }
+        #[test]
+        fn missing_cid_is_valid() {
+            assert!(Policy::strict().is_valid(&Passport {
+                fields: vec![
+                    PassportField::EyeColor(String::from("gry")),
+                    PassportField::PassportId(String::from("860033327")),
+                    PassportField::ExpirationYear(2020),
+                    PassportField::HairColor(String::from("#fffffd")),
+                    PassportField::BirthYear(1937),
+                    PassportField::IssueYear(2017),
+                    PassportField::Height(Height::Cm(183.0)),
+                ],
+            }));
+        }
+        #[test]
+        fn missing_multiple_fields_is_not_valid() {
+            assert!(!Policy::strict().is_valid(&Passport {
+                fields: vec![
+                    PassportField::EyeColor(String::from("gry")),
+                    PassportField::PassportId(String::from("860033327")),
+                    PassportField::ExpirationYear(2020),
+                    PassportField::HairColor(String::from("#fffffd")),
+                    PassportField::IssueYear(2017),
+                    PassportField::Height(Height::Cm(183.0)),
+                    // Missing country id (ok) and birth year (not ok)
+                ],
+            }));
+        }
+    }
+
+    mod policy {
+        use super::super::*;
+
+        fn sloppy_passport() -> Passport {
+            Passport {
+                fields: vec![
+                    PassportField::EyeColor(String::from("xyz")),
+                    PassportField::PassportId(String::from("0123")),
+                    PassportField::ExpirationYear(1999),
+                    PassportField::HairColor(String::from("red")),
+                    PassportField::BirthYear(1850),
+                    PassportField::IssueYear(2017),
+                    PassportField::Height(Height::In(250.0)),
+                ],
+            }
+        }
+
+        #[test]
+        fn builtin_policies() {
+            assert!(Policy::builtin("presence").is_some());
+            assert!(Policy::builtin("strict").is_some());
+            assert!(Policy::builtin("lenient").is_none());
+        }
+
+        #[test]
+        fn presence_only_checks_fields_exist() {
+            let presence = Policy::builtin("presence").unwrap();
+            assert!(presence.is_valid(&sloppy_passport()));
+            assert!(!Policy::strict().is_valid(&sloppy_passport()));
+
+            let mut missing = sloppy_passport();
+            missing.fields.pop();
+            assert!(!presence.is_valid(&missing));
+        }
+
+        #[test]
+        fn presence_ignores_values() {
+            let presence = Policy::builtin("presence").unwrap();
+            let (passports, diagnostics) = parse_batch(
+                "byr:1937 iyr:2017 eyr:2020 hgt:190 hcl:z ecl:gry pid:1\n\n\
+                 byr:19 iyr:2017 eyr:2020 hgt:59 hcl:#fffffd ecl:grn pid:860033327",
+                ParseMode::Lenient,
+            );
+            assert_eq!(diagnostics.len(), 3);
+            assert_eq!(passports.len(), 2);
+            for p in &passports {
+                assert_eq!(presence.check(&p.passport), vec![]);
+                assert!(!Policy::strict().is_valid(&p.passport));
+            }
+        }
+
+        #[test]
+        fn duplicate_fields_are_not_valid() {
+            let presence = Policy::builtin("presence").unwrap();
+            let mut duplicate = sloppy_passport();
+            duplicate.fields.push(PassportField::BirthYear(1990));
+            assert!(!presence.is_valid(&duplicate));
+        }
+
+        #[test]
+        fn custom_policy() {
+            let policy = Policy::parse(
+                r##"
+                [hgt]
+                type = "height"
+                required = true
+                min_cm = 100
+
+                [hcl]
+                type = "text"
+                pattern = "#[a-z]+"
+                "##,
+            )
+            .unwrap();
+
+            let passport = |fields| Passport { fields };
+            assert!(policy.is_valid(&passport(vec![PassportField::Height(Height::Cm(100.0))])));
+            assert!(!policy.is_valid(&passport(vec![PassportField::Height(Height::In(10.0))])));
+            assert!(!policy.is_valid(&passport(vec![PassportField::Height(Height::Cm(99.0))])));
+            assert!(!policy.is_valid(&passport(vec![
+                PassportField::Height(Height::Cm(150.0)),
+                PassportField::HairColor(String::from("red")),
+            ])));
+            assert!(!policy.is_valid(&passport(vec![])));
+        }
+
+        #[test]
+        fn height_range_in_cm() {
+            let policy = Policy::parse(
+                r##"
+                [hgt]
+                type = "height"
+                required = true
+                min = 150
+                max = 193
+                max_in = 70
+                "##,
+            )
+            .unwrap();
+
+            let valid = |h| {
+                policy.is_valid(&Passport {
+                    fields: vec![PassportField::Height(h)],
+                })
+            };
+            assert!(valid(Height::Mm(1500.0)));
+            assert!(valid(Height::M(1.93)));
+            assert!(valid(Height::FtIn(5, 11.0)));
+            assert!(!valid(Height::FtIn(6, 6.0)));
+            assert!(!valid(Height::In(59.0)));
+            assert!(valid(Height::In(60.0)));
+            assert!(!valid(Height::In(71.0)));
+        }
+
+        #[test]
+        fn heights_in_units_without_a_range() {
+            let policy = |units| {
+                Policy::parse(&format!(
+                    "[hgt]\ntype = \"height\"\nmin_cm = 150\nmax_cm = 193\n{}",
+                    units
+                ))
+                .unwrap()
+            };
+            let valid = |policy: &Policy, h| {
+                policy.is_valid(&Passport {
+                    fields: vec![PassportField::Height(h)],
+                })
+            };
+
+            let implicit = policy("");
+            assert!(valid(&implicit, Height::Cm(180.0)));
+            assert!(!valid(&implicit, Height::Mm(1.0)));
+            assert!(!valid(&implicit, Height::M(3.0)));
+            assert!(!valid(&implicit, Height::FtIn(6, 0.0)));
+
+            let explicit = policy("units = [\"cm\", \"m\"]");
+            assert!(valid(&explicit, Height::M(3.0)));
+            assert!(!valid(&explicit, Height::Mm(1.0)));
+        }
+
+        #[test]
+        fn parse_errors() {
             assert_eq!(
-                Passport {
-                    fields: vec![
-                        PassportField::EyeColor(String::from("gry")),
-                        PassportField::PassportId(String::from("860033327")),
-                        PassportField::ExpirationYear(2020),
-                        PassportField::HairColor(String::from("#fffffd")),
-                        PassportField::BirthYear(1937),
-                        PassportField::IssueYear(2017),
-                        PassportField::CountryId(String::from("147")),
-                        PassportField::Height(Height::Cm(183)),
-                    ],
-                }
-                .is_valid(),
-                true,
+                Policy::parse("[hgt]\ntype = \"height\"\nmin_ft = 1").unwrap_err(),
+                err::ParseError::new("unknown height unit", "min_ft").on_line(3),
+            );
+            assert_eq!(
+                Policy::parse("[hgt]\ntype = \"height\"\nunits = [\"cm\", \"ft\"]").unwrap_err(),
+                err::ParseError::new("unknown height unit", "ft").on_line(3),
+            );
+            assert_eq!(
+                Policy::parse("[hgt]\ntype = \"height\"\nminimum = 1").unwrap_err(),
+                err::ParseError::new("unknown setting for field type", "minimum").on_line(3),
+            );
+            assert_eq!(
+                Policy::parse("[abc]\ntype = \"text\"").unwrap_err(),
+                err::ParseError::new("unknown field", "abc").on_line(1),
+            );
+            assert_eq!(
+                Policy::parse("[byr]\nrequired = true").unwrap_err(),
+                err::ParseError::new("missing field type", "byr").on_line(1),
+            );
+            assert_eq!(
+                Policy::parse("[byr]\ntype = \"date\"").unwrap_err(),
+                err::ParseError::new("unknown field type", "date").on_line(1),
+            );
+            assert_eq!(
+                Policy::parse("[byr]\ntype = \"year\"\nrequired = 1").unwrap_err(),
+                err::ParseError::new("invalid value for", "required").on_line(3),
+            );
+            assert_eq!(
+                Policy::parse("[hcl]\ntype = \"text\"\nmin = 1").unwrap_err(),
+                err::ParseError::new("unknown setting for field type", "min").on_line(3),
+            );
+            assert_eq!(
+                Policy::parse("[hcl]\ntype = \"text\"\npattern = \"[\"").unwrap_err(),
+                err::ParseError::new("invalid value for", "pattern").on_line(3),
             );
         }
+    }
+
+    mod report {
+        use super::super::*;
+
+        const BATCH: &str = "ecl:gry pid:860033327 eyr:2020 hcl:#fffffd
+byr:1937 iyr:2017 cid:147 hgt:183cm
+
+iyr:2013 ecl:amb cid:350 eyr:2023 pid:028048884
+hcl:#cfa07d byr:1929
+
+hcl:#ae17e1 iyr:2013 eyr:2024 ecl:brn pid:760753108
+byr:1931 hgt:179cm byr:1931
+
+byr:1850 hcl:#cfa07d eyr:2025 pid:166559648 iyr:2011
+ecl:xyz hgt:59in
+";
+
         #[test]
-        fn missing_field_is_not_valid() {
+        fn split_passports_lines() {
+            let passports = split_passports(BATCH);
             assert_eq!(
-                Passport {
-                    fields: vec![
-                        PassportField::EyeColor(String::from("gry")),
-                        PassportField::PassportId(String::from("860033327")),
-                        PassportField::ExpirationYear(2020),
-                        PassportField::HairColor(String::from("#fffffd")),
-                        PassportField::BirthYear(1937),
-                        PassportField::IssueYear(2017),
-                        PassportField::CountryId(String::from("147")),
-                        // Height missing
-                    ],
-                }
-                .is_valid(),
-                false,
+                passports.iter().map(|(l, _)| *l).collect::<Vec<usize>>(),
+                vec![1, 4, 7, 10],
+            );
+            assert_eq!(
+                passports[1].1,
+                "iyr:2013 ecl:amb cid:350 eyr:2023 pid:028048884\nhcl:#cfa07d byr:1929",
+            );
+        }
+
+        #[test]
+        fn violations() {
+            let report = ValidationReport::validate(BATCH, Policy::strict(), ParseMode::Strict);
+            assert_eq!(
+                report.results,
+                vec![
+                    PassportResult {
+                        index: 1,
+                        line: 1,
+                        violations: vec![],
+                    },
+                    PassportResult {
+                        index: 2,
+                        line: 4,
+                        violations: vec![Violation::Missing(String::from("hgt"))],
+                    },
+                    PassportResult {
+                        index: 3,
+                        line: 7,
+                        violations: vec![Violation::Duplicate(String::from("byr"), 2)],
+                    },
+                    PassportResult {
+                        index: 4,
+                        line: 10,
+                        violations: vec![
+                            Violation::OutOfRange(String::from("byr"), String::from("1850")),
+                            Violation::BadFormat(String::from("ecl"), String::from("xyz")),
+                        ],
+                    },
+                ],
             );
         }
+
         #[test]
-        fn missing_cid_is_valid() {
+        fn summary() {
+            let report = ValidationReport::validate(BATCH, Policy::strict(), ParseMode::Strict);
+            assert_eq!(report.valid(), 1);
+            assert_eq!(
+                report
+                    .histogram()
+                    .into_iter()
+                    .collect::<Vec<(&str, usize)>>(),
+                vec![
+                    ("bad format", 1),
+                    ("duplicate field", 1),
+                    ("missing field", 1),
+                    ("out of range", 1),
+                ],
+            );
+            assert_eq!(
+                report.to_string(),
+                "passport 2 (line 4): missing field hgt
+passport 3 (line 7): duplicate field byr (2 times)
+passport 4 (line 10): out of range byr: 1850, bad format ecl: xyz
+valid passports: 1 of 4
+bad format: 1
+duplicate field: 1
+missing field: 1
+out of range: 1
+",
+            );
+        }
+    }
+
+    mod lenient {
+        use super::super::*;
+
+        const BATCH: &str = "ecl:gry pid:860033327 eyr:2020 hcl:#fffffd
+byr:1937 iyr:2017 cid:147 hgt:183cm
+
+iyr:2013 ecl:amb cid:350 eyr:2023 pid:028048884
+hcl:#cfa07d byr:19x9 hgt:6ft
+foo:bar
+
+hcl:#ae17e1 iyr:2013 eyr:2024 ecl:brn pid:760753108
+byr:1931 hgt:179cm garbage
+";
+
+        #[test]
+        fn parse_invalid_fields() {
             assert_eq!(
+                PassportField::parse_lenient("byr:19x9"),
+                (
+                    PassportField::Invalid {
+                        key: String::from("byr"),
+                        raw: String::from("19x9"),
+                        reason: String::from("invalid format"),
+                    },
+                    Some(err::ParseError::new("invalid format", "byr:19x9")),
+                ),
+            );
+            assert_eq!(
+                PassportField::parse_lenient("garbage").0,
+                PassportField::Invalid {
+                    key: String::from("garbage"),
+                    raw: String::new(),
+                    reason: String::from("invalid field format"),
+                },
+            );
+            assert_eq!(
+                PassportField::parse_lenient("cid:1"),
+                (PassportField::CountryId(String::from("1")), None),
+            );
+        }
+
+        #[test]
+        fn strict_drops_passports() {
+            let (passports, diagnostics) = parse_batch(BATCH, ParseMode::Strict);
+            assert_eq!(
+                passports.iter().map(|p| p.index).collect::<Vec<usize>>(),
+                vec![1],
+            );
+            assert_eq!(diagnostics.len(), 4);
+            assert_eq!(parse_passports(BATCH, ParseMode::Strict).unwrap().len(), 1);
+        }
+
+        #[test]
+        fn lenient_keeps_passports() {
+            let (passports, diagnostics) = parse_batch(BATCH, ParseMode::Lenient);
+            assert_eq!(passports.len(), 3);
+            assert_eq!(passports[1].passport.fields.len(), 9);
+            assert_eq!(
+                diagnostics,
+                vec![
+                    err::ParseError::new("invalid format", "byr:19x9").on_line(5),
+                    err::ParseError::new("invalid format", "hgt:6ft").on_line(5),
+                    err::ParseError::new("unknown field", "foo:bar").on_line(6),
+                    err::ParseError::new("invalid field format", "garbage").on_line(9),
+                ],
+            );
+        }
+
+        #[test]
+        fn invalid_fields_fail_validation() {
+            let report = ValidationReport::validate(BATCH, Policy::strict(), ParseMode::Lenient);
+            assert_eq!(report.valid(), 1);
+            assert_eq!(
+                report.results[1].violations,
+                vec![
+                    Violation::Invalid(String::from("byr"), String::from("invalid format")),
+                    Violation::Invalid(String::from("hgt"), String::from("invalid format")),
+                    Violation::Invalid(String::from("foo"), String::from("unknown field")),
+                ],
+            );
+            assert_eq!(
+                report.results[2].violations,
+                vec![Violation::Invalid(
+                    String::from("garbage"),
+                    String::from("invalid field format"),
+                )],
+            );
+
+            let presence = Policy::builtin("presence").unwrap();
+            let report = ValidationReport::validate(BATCH, &presence, ParseMode::Lenient);
+            assert_eq!(report.valid(), 1);
+        }
+    }
+
+    mod serialize {
+        use super::super::*;
+
+        const BATCH: &str = "ecl:gry pid:860033327 eyr:2020 hcl:#fffffd
+byr:1937 iyr:2017 cid:147 hgt:183cm
+
+iyr:2013 ecl:amb cid:350 eyr:2023 pid:028048884
+hcl:#cfa07d byr:1929 hgt:5'11\"
+
+hcl:#ae17e1 iyr:2013 eyr:2024 ecl:brn
+byr:1931 byr:1932 hgt:1.79m
+";
+
+        fn batch() -> Vec<Passport> {
+            parse_passports(BATCH, ParseMode::Strict).unwrap()
+        }
+
+        #[test]
+        fn puzzle_round_trip() {
+            let written = write_batch(&batch(), BatchFormat::Puzzle);
+            assert_eq!(
+                written,
+                "ecl:gry pid:860033327 eyr:2020 hcl:#fffffd byr:1937 iyr:2017 cid:147 hgt:183cm
+
+iyr:2013 ecl:amb cid:350 eyr:2023 pid:028048884 hcl:#cfa07d byr:1929 hgt:5'11\"
+
+hcl:#ae17e1 iyr:2013 eyr:2024 ecl:brn byr:1931 byr:1932 hgt:1.79m
+",
+            );
+            assert_eq!(
+                parse_passports(&written, ParseMode::Strict).unwrap(),
+                batch()
+            );
+        }
+
+        #[test]
+        fn json() {
+            let passports = vec![
                 Passport {
                     fields: vec![
-                        PassportField::EyeColor(String::from("gry")),
-                        PassportField::PassportId(String::from("860033327")),
-                        PassportField::ExpirationYear(2020),
-                        PassportField::HairColor(String::from("#fffffd")),
                         PassportField::BirthYear(1937),
-                        PassportField::IssueYear(2017),
-                        PassportField::Height(Height::Cm(183)),
+                        PassportField::Height(Height::FtIn(5, 11.0)),
                     ],
-                }
-                .is_valid(),
-                true,
+                },
+                Passport { fields: vec![] },
+            ];
+            assert_eq!(
+                write_batch(&passports, BatchFormat::Json),
+                "[{\"byr\":\"1937\",\"hgt\":\"5'11\\\"\"},{}]\n",
             );
         }
+
         #[test]
-        fn missing_multiple_fields_is_not_valid() {
+        fn csv() {
+            let written = write_batch(&batch(), BatchFormat::Csv);
             assert_eq!(
-                Passport {
+                written,
+                "byr,iyr,eyr,hgt,hcl,ecl,pid,cid
+1937,2017,2020,183cm,#fffffd,gry,860033327,147
+1929,2013,2023,\"5'11\"\"\",#cfa07d,amb,028048884,350
+1931 1932,2013,2024,1.79m,#ae17e1,brn,,
+",
+            );
+
+            let read = read_csv(&written, ParseMode::Strict).unwrap();
+            assert_eq!(read.len(), 3);
+            for (passport, expected) in read.iter().zip(batch().iter()) {
+                assert!(expected.fields.iter().all(|f| passport.fields.contains(f)));
+                assert_eq!(passport.fields.len(), expected.fields.len());
+            }
+        }
+
+        #[test]
+        fn csv_unknown_keys() {
+            let (passports, _) = parse_batch("byr:1990 foo:bar\n\nfoo:baz", ParseMode::Lenient);
+            let passports: Vec<Passport> = passports.into_iter().map(|p| p.passport).collect();
+            assert_eq!(write_csv(&passports), "byr,foo\n1990,bar\n,baz\n");
+        }
+
+        #[test]
+        fn csv_keys_with_separators() {
+            let (passports, _) = parse_batch("byr:1990 a,b:1 \"q:2\n\nbyr:x", ParseMode::Lenient);
+            let passports: Vec<Passport> = passports.into_iter().map(|p| p.passport).collect();
+            let written = write_csv(&passports);
+            assert_eq!(written, "byr,\"a,b\",\"\"\"q\"\n1990,1,2\nx,,\n");
+            assert_eq!(read_csv(&written, ParseMode::Lenient).unwrap(), passports);
+        }
+
+        #[test]
+        fn csv_errors() {
+            assert_eq!(
+                read_csv("byr,iyr\n1990", ParseMode::Strict),
+                Err(err::ParseError::new("wrong number of cells", "1990").on_line(2)),
+            );
+            assert_eq!(
+                read_csv("byr,iyr\n1990,\"2010", ParseMode::Strict),
+                Err(err::ParseError::new("unterminated quote", "1990,\"2010").on_line(2)),
+            );
+            assert_eq!(
+                read_csv("byr,iyr\n\n1990,twenty", ParseMode::Strict),
+                Err(err::ParseError::new("invalid format", "iyr:twenty").on_line(3)),
+            );
+            assert_eq!(
+                read_csv("byr,iyr\n\n1990,twenty", ParseMode::Lenient),
+                Ok(vec![Passport {
                     fields: vec![
-                        PassportField::EyeColor(String::from("gry")),
-                        PassportField::PassportId(String::from("860033327")),
-                        PassportField::ExpirationYear(2020),
-                        PassportField::HairColor(String::from("#fffffd")),
-                        PassportField::IssueYear(2017),
-                        PassportField::Height(Height::Cm(183)),
-                        // Missing country id (ok) and birth year (not ok)
+                        PassportField::BirthYear(1990),
+                        PassportField::Invalid {
+                            key: String::from("iyr"),
+                            raw: String::from("twenty"),
+                            reason: String::from("invalid format"),
+                        },
                     ],
+                }]),
+            );
+            assert_eq!(
+                parse_csv_line("a,\"b,\"\"c\"\"\",,d"),
+                Ok(vec![
+                    String::from("a"),
+                    String::from("b,\"c\""),
+                    String::new(),
+                    String::from("d"),
+                ]),
+            );
+        }
+    }
+
+    // Builds random passport batches along with what the validators should
+    // say about each passport, so the two can be checked against each other.
+    mod generated {
+        use super::super::*;
+
+        // xorshift64*, plenty for picking test values.
+        struct Rng(u64);
+
+        impl Rng {
+            fn new(seed: u64) -> Rng {
+                // The state can never be zero.
+                Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+            }
+
+            fn next(&mut self) -> u64 {
+                self.0 ^= self.0 >> 12;
+                self.0 ^= self.0 << 25;
+                self.0 ^= self.0 >> 27;
+                self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+            }
+
+            // Inclusive on both ends.
+            fn range(&mut self, lo: u64, hi: u64) -> u64 {
+                lo + self.next() % (hi - lo + 1)
+            }
+
+            fn chance(&mut self, p: f64) -> bool {
+                ((self.next() >> 11) as f64 / (1u64 << 53) as f64) < p
+            }
+
+            fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+                items[self.range(0, items.len() as u64 - 1) as usize]
+            }
+        }
+
+        // How often each field is left out, and how often one that's there
+        // holds a bad value.
+        #[derive(Clone, Copy)]
+        struct Mix {
+            invalid: f64,
+            missing: f64,
+        }
+
+        #[derive(PartialEq, Clone, Copy)]
+        enum Quality {
+            Valid,
+            // Parses but breaks the strict policy.
+            Broken,
+            Unparsable,
+        }
+
+        struct Generated {
+            text: String,
+            // Expected from the strict policy.
+            valid: bool,
+            // Expected from the presence policy.
+            present: bool,
+            // Strict parsing drops fields it can't read.
+            unparsable: bool,
+        }
+
+        fn year(rng: &mut Rng, lo: u64, hi: u64, quality: Quality) -> String {
+            match quality {
+                Quality::Valid => rng.range(lo, hi).to_string(),
+                Quality::Broken if rng.chance(0.5) => rng.range(lo - 30, lo - 1).to_string(),
+                Quality::Broken => rng.range(hi + 1, hi + 30).to_string(),
+                Quality::Unparsable => rng
+                    .pick(&["abcd", "19x0", "999", "02000", "+200"])
+                    .to_owned(),
+            }
+        }
+
+        fn height(rng: &mut Rng, quality: Quality) -> String {
+            match quality {
+                Quality::Valid if rng.chance(0.5) => format!("{}cm", rng.range(150, 193)),
+                Quality::Valid => format!("{}in", rng.range(59, 76)),
+                Quality::Broken => match rng.range(0, 4) {
+                    0 => format!("{}cm", rng.range(100, 149)),
+                    1 => format!("{}cm", rng.range(194, 230)),
+                    2 => format!("{}in", rng.range(30, 58)),
+                    3 => format!("{}in", rng.range(77, 90)),
+                    _ => rng.pick(&["1700mm", "1.8m", "5'10\""]).to_owned(),
+                },
+                Quality::Unparsable => rng.pick(&["190", "tall", "12ft", "cm"]).to_owned(),
+            }
+        }
+
+        fn digits(rng: &mut Rng, count: usize) -> String {
+            (0..count).map(|_| rng.range(0, 9).to_string()).collect()
+        }
+
+        fn hair_color(rng: &mut Rng, quality: Quality) -> String {
+            let hex: String = (0..6)
+                .map(|_| rng.pick(&["0", "3", "7", "9", "a", "c", "f"]))
+                .collect();
+            match quality {
+                Quality::Valid => format!("#{}", hex),
+                _ => match rng.range(0, 4) {
+                    0 => hex,
+                    1 => format!("#{}", &hex[1..]),
+                    2 => format!("#{}z", hex),
+                    3 => format!("#{}", hex.to_uppercase().replace('0', "A")),
+                    _ => format!("#{}g", &hex[1..]),
+                },
+            }
+        }
+
+        fn eye_color(rng: &mut Rng, quality: Quality) -> String {
+            match quality {
+                Quality::Valid => rng.pick(&["amb", "blu", "brn", "gry", "grn", "hzl", "oth"]),
+                _ => rng.pick(&["xyz", "BRN", "brnn", "gr", "#123abc"]),
+            }
+            .to_owned()
+        }
+
+        fn passport_id(rng: &mut Rng, quality: Quality) -> String {
+            match quality {
+                Quality::Valid => digits(rng, 9),
+                _ => match rng.range(0, 3) {
+                    0 => digits(rng, 8),
+                    1 => digits(rng, 10),
+                    2 => format!("{}a", digits(rng, 8)),
+                    _ => format!("+{}", digits(rng, 8)),
+                },
+            }
+        }
+
+        fn generate_one(rng: &mut Rng, mix: Mix) -> Generated {
+            let mut fields = Vec::new();
+            let mut valid = true;
+            let mut present = true;
+            let mut unparsable = false;
+
+            for key in &["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid"] {
+                if rng.chance(mix.missing) {
+                    valid = false;
+                    present = false;
+                    continue;
+                }
+
+                let parses_badly = matches!(*key, "byr" | "iyr" | "eyr" | "hgt");
+                let quality = if !rng.chance(mix.invalid) {
+                    Quality::Valid
+                } else if parses_badly && rng.chance(0.5) {
+                    Quality::Unparsable
+                } else {
+                    Quality::Broken
+                };
+                valid &= quality == Quality::Valid;
+                unparsable |= quality == Quality::Unparsable;
+
+                let value = match *key {
+                    "byr" => year(rng, 1920, 2002, quality),
+                    "iyr" => year(rng, 2010, 2020, quality),
+                    "eyr" => year(rng, 2020, 2030, quality),
+                    "hgt" => height(rng, quality),
+                    "hcl" => hair_color(rng, quality),
+                    "ecl" => eye_color(rng, quality),
+                    _ => passport_id(rng, quality),
+                };
+                fields.push(format!("{}:{}", key, value));
+            }
+            if !rng.chance(mix.missing) {
+                fields.push(format!("cid:{}", rng.range(1, 999)));
+            }
+
+            for i in (1..fields.len()).rev() {
+                let j = rng.range(0, i as u64) as usize;
+                fields.swap(i, j);
+            }
+            let mut text = String::new();
+            for (idx, field) in fields.iter().enumerate() {
+                if idx > 0 {
+                    text.push(if rng.chance(0.2) { '\n' } else { ' ' });
                 }
-                .is_valid(),
-                false,
+                text += field;
+            }
+
+            Generated {
+                text,
+                valid,
+                present,
+                unparsable,
+            }
+        }
+
+        fn generate(seed: u64, count: usize, mix: Mix) -> Vec<Generated> {
+            let mut rng = Rng::new(seed);
+            (0..count).map(|_| generate_one(&mut rng, mix)).collect()
+        }
+
+        fn batch_text(generated: &[Generated]) -> String {
+            let texts: Vec<&str> = generated.iter().map(|g| g.text.as_str()).collect();
+            texts.join("\n\n") + "\n"
+        }
+
+        const MIX: Mix = Mix {
+            invalid: 0.08,
+            missing: 0.05,
+        };
+
+        #[test]
+        fn same_seed_same_batch() {
+            assert_eq!(
+                batch_text(&generate(7, 20, MIX)),
+                batch_text(&generate(7, 20, MIX)),
+            );
+            assert_ne!(
+                batch_text(&generate(7, 20, MIX)),
+                batch_text(&generate(8, 20, MIX)),
+            );
+        }
+
+        #[test]
+        fn ratios() {
+            let clean = generate(
+                1,
+                50,
+                Mix {
+                    invalid: 0.0,
+                    missing: 0.0,
+                },
+            );
+            assert!(clean.iter().all(|g| g.valid && g.present));
+
+            let empty = generate(
+                1,
+                50,
+                Mix {
+                    invalid: 0.0,
+                    missing: 1.0,
+                },
             );
+            assert!(empty.iter().all(|g| g.text.is_empty() && !g.present));
+
+            let broken = generate(
+                1,
+                50,
+                Mix {
+                    invalid: 1.0,
+                    missing: 0.0,
+                },
+            );
+            assert!(broken.iter().all(|g| !g.valid && g.present));
+        }
+
+        #[test]
+        fn policies_match_oracle() {
+            let presence = Policy::builtin("presence").unwrap();
+            for seed in 0..25 {
+                let generated = generate(seed, 40, MIX);
+                let (passports, _) = parse_batch(&batch_text(&generated), ParseMode::Lenient);
+                assert_eq!(passports.len(), generated.len());
+
+                for (parsed, expected) in passports.iter().zip(generated.iter()) {
+                    assert_eq!(
+                        Policy::strict().is_valid(&parsed.passport),
+                        expected.valid,
+                        "seed {}, strict: {}",
+                        seed,
+                        expected.text,
+                    );
+                    assert_eq!(
+                        presence.is_valid(&parsed.passport),
+                        expected.present,
+                        "seed {}, presence: {}",
+                        seed,
+                        expected.text,
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn strict_parsing_matches_oracle() {
+            let presence = Policy::builtin("presence").unwrap();
+            for seed in 0..25 {
+                let generated = generate(seed, 40, MIX);
+                let passports =
+                    parse_passports(&batch_text(&generated), ParseMode::Strict).unwrap();
+                assert_eq!(
+                    passports
+                        .iter()
+                        .filter(|p| Policy::strict().is_valid(p))
+                        .count(),
+                    generated.iter().filter(|g| g.valid).count(),
+                );
+                assert_eq!(
+                    passports.iter().filter(|p| presence.is_valid(p)).count(),
+                    generated
+                        .iter()
+                        .filter(|g| g.present && !g.unparsable)
+                        .count(),
+                );
+            }
+        }
+
+        #[test]
+        fn round_trips_keep_validity() {
+            for seed in 0..10 {
+                let generated = generate(seed, 40, MIX);
+                let (parsed, _) = parse_batch(&batch_text(&generated), ParseMode::Lenient);
+                let passports: Vec<Passport> = parsed.into_iter().map(|p| p.passport).collect();
+                let expected: Vec<bool> = generated.iter().map(|g| g.valid).collect();
+
+                let (puzzle, _) = parse_batch(&write_puzzle(&passports), ParseMode::Lenient);
+                let csv = read_csv(&write_csv(&passports), ParseMode::Lenient).unwrap();
+                assert_eq!(
+                    puzzle
+                        .iter()
+                        .map(|p| Policy::strict().is_valid(&p.passport))
+                        .collect::<Vec<bool>>(),
+                    expected,
+                );
+                assert_eq!(
+                    csv.iter()
+                        .map(|p| Policy::strict().is_valid(p))
+                        .collect::<Vec<bool>>(),
+                    expected,
+                );
+            }
         }
     }
 }
@@ -9,20 +9,24 @@ const PLANE_COLS: u32 = 8;
 
 #[derive(Debug)]
 pub struct Day{}
-impl days::Day for Day {
-    fn run(&self) {
-        println!("running day 5");
+impl Day {
+    fn plane(&self) -> Plane {
+        match Plane::new(PLANE_ROWS, PLANE_COLS) {
+            Ok(plane) => plane,
+            Err(e) => panic!("{:?}", e),
+        }
+    }
+
+    fn load_ids(&self, plane: &Plane) -> Vec<u32> {
         let mut file = match File::open("data/05/input.txt") {
             Ok(file) => file,
-            Err(e) => panic!(e),
+            Err(e) => panic!("{:?}", e),
         };
         let mut contents = String::new();
-        match file.read_to_string(&mut contents) {
-            Err(e) => panic!(e),
-            _ => {}
-        };
+        if let Err(e) = file.read_to_string(&mut contents) {
+            panic!("{:?}", e)
+        }
 
-        let plane = Plane::new(PLANE_ROWS, PLANE_COLS);
         let lines: Vec<&str> = contents.split("\n").map(|l| l.trim()).collect();
 
         let mut ids: Vec<u32> = Vec::new();
@@ -33,6 +37,15 @@ impl days::Day for Day {
             }
         }
         ids.sort();
+        ids
+    }
+}
+
+impl days::Day for Day {
+    fn run(&self) {
+        println!("running day 5");
+        let plane = self.plane();
+        let ids = self.load_ids(&plane);
 
         let high_id = ids
             .iter()
@@ -42,17 +55,45 @@ impl days::Day for Day {
             .fold(high_id, |acc, id| if *id < acc { *id } else { acc });
         println!("id range: {} - {}", low_id, high_id);
 
-        for idx in 1..ids.len() - 1 {
-            let id = ids[idx];
-            let next_id = ids[idx + 1];
+        for id in missing_ids(&ids) {
+            println!("missing id: {}", id);
+        }
+    }
 
-            if next_id != id + 1 {
-                println!("missing id: {}", id + 1);
+    // Usage: 5 map
+    //        5 encode <id>
+    fn run_with_args(&self, args: &[String]) {
+        let plane = self.plane();
+        match args.first().map(|a| a.as_str()) {
+            Some("map") => {
+                let ids = self.load_ids(&plane);
+                let ours = missing_ids(&ids).first().copied();
+                print!("{}", SeatMap::new(&plane, &ids, ours));
             }
+            Some("encode") => match args.get(1).map(|id| id.parse::<u32>()) {
+                Some(Ok(id)) => match plane.seat(id) {
+                    Some(seat) => println!("{}", plane.encode(&seat)),
+                    None => println!("no seat with id {}", id),
+                },
+                Some(Err(e)) => println!("could not parse id: {}", e),
+                None => println!("missing id"),
+            },
+            Some(cmd) => println!("unknown command: {}", cmd),
+            None => self.run(),
         }
     }
 }
 
+// Ids just after a gap in the sorted ids, skipping the first id as the
+// front of the plane might be missing.
+fn missing_ids(ids: &[u32]) -> Vec<u32> {
+    ids.windows(2)
+        .skip(1)
+        .filter(|pair| pair[1] != pair[0] + 1)
+        .map(|pair| pair[0] + 1)
+        .collect()
+}
+
 #[derive(PartialEq, Debug)]
 struct Seat {
     row: u32,
@@ -61,7 +102,7 @@ struct Seat {
 
 impl Seat {
     fn new(row: u32, col: u32) -> Seat {
-        Seat { row: row, col: col }
+        Seat { row, col }
     }
     fn row(&self) -> u32 {
         self.row
@@ -71,49 +112,139 @@ impl Seat {
     }
 }
 
+// A boarding pass is a binary number, one F/B character per bit of the
+// row followed by one L/R character per bit of the column, so both
+// dimensions have to be powers of two.
 struct Plane {
     rows: u32,
     cols: u32,
 }
 
 impl Plane {
-    fn new(rows: u32, cols: u32) -> Plane {
-        Plane {
-            rows: rows,
-            cols: cols,
+    fn new(rows: u32, cols: u32) -> Result<Plane, err::ParseError> {
+        if !rows.is_power_of_two() {
+            return Err(err::ParseError::new(
+                "rows is not a power of two",
+                &rows.to_string(),
+            ));
+        }
+        if !cols.is_power_of_two() {
+            return Err(err::ParseError::new(
+                "cols is not a power of two",
+                &cols.to_string(),
+            ));
         }
+        if rows.checked_mul(cols).is_none() {
+            let dims = format!("{}x{}", rows, cols);
+            return Err(err::ParseError::new("too many seats", &dims));
+        }
+
+        Ok(Plane { rows, cols })
+    }
+
+    fn row_bits(&self) -> u32 {
+        self.rows.trailing_zeros()
+    }
+
+    fn col_bits(&self) -> u32 {
+        self.cols.trailing_zeros()
     }
 
     fn find_seat(&self, seat: &str) -> Result<Seat, err::ParseError> {
         let clean_seat = seat.trim().to_uppercase();
-
-        let seat_regex = regex::Regex::new(r"^[FB]{7}[RL]{3}$").unwrap();
-        if !seat_regex.is_match(&clean_seat) {
+        if clean_seat.chars().count() != (self.row_bits() + self.col_bits()) as usize {
             return Err(err::ParseError::new("invalid seat format", seat));
         }
 
-        let mut row_front: f64 = 0.0;
-        let mut row_back: f64 = (self.rows - 1) as f64;
-        let mut col_left: f64 = 0.0;
-        let mut col_right: f64 = (self.cols - 1) as f64;
-
-        for p in clean_seat.chars() {
-            match p {
-                'F' => row_back = (row_back - ((row_back - row_front) / 2.0)).floor(),
-                'B' => row_front = (row_front + ((row_back - row_front) / 2.0)).ceil(),
-                'L' => col_right = (col_right - ((col_right - col_left) / 2.0)).floor(),
-                'R' => col_left = (col_right - ((col_right - col_left) / 2.0)).ceil(),
-                _ => return Err(err::ParseError::new("invalid seat format", seat)),
+        let mut row = 0;
+        let mut col = 0;
+        for (idx, p) in clean_seat.chars().enumerate() {
+            let is_row = idx < self.row_bits() as usize;
+            match (is_row, p) {
+                (true, 'F') => row <<= 1,
+                (true, 'B') => row = row << 1 | 1,
+                (false, 'L') => col <<= 1,
+                (false, 'R') => col = col << 1 | 1,
+                _ => return Err(err::ParseError::at("invalid seat format", seat, idx + 1)),
             };
-            // println!("{}: {} - {}, {} - {}", p, row_front, row_back, col_left, col_right);
         }
 
-        Ok(Seat::new(row_front as u32, col_left as u32))
+        Ok(Seat::new(row, col))
+    }
+
+    // The pass for a seat on this plane, the reverse of find_seat.
+    fn encode(&self, seat: &Seat) -> String {
+        assert!(
+            seat.row() < self.rows && seat.col() < self.cols,
+            "seat {:?} is not on the plane",
+            seat
+        );
+
+        let row =
+            (0..self.row_bits())
+                .rev()
+                .map(|bit| if seat.row() >> bit & 1 == 1 { 'B' } else { 'F' });
+        let col =
+            (0..self.col_bits())
+                .rev()
+                .map(|bit| if seat.col() >> bit & 1 == 1 { 'R' } else { 'L' });
+        row.chain(col).collect()
     }
 
     fn seat_id(&self, seat: &Seat) -> u32 {
         seat.row() * self.cols + seat.col()
     }
+
+    // The seat with the id, or None if the plane doesn't have that many.
+    fn seat(&self, id: u32) -> Option<Seat> {
+        if id >= self.rows * self.cols {
+            return None;
+        }
+        Some(Seat::new(id / self.cols, id % self.cols))
+    }
+}
+
+// Every seat on a plane, one row per line, as # for taken seats, . for
+// empty ones and O for ours.
+struct SeatMap<'a> {
+    plane: &'a Plane,
+    taken: Vec<bool>,
+    ours: Option<u32>,
+}
+
+impl<'a> SeatMap<'a> {
+    fn new(plane: &'a Plane, ids: &[u32], ours: Option<u32>) -> SeatMap<'a> {
+        let mut taken = vec![false; (plane.rows * plane.cols) as usize];
+        for id in ids {
+            if let Some(seat) = taken.get_mut(*id as usize) {
+                *seat = true;
+            }
+        }
+
+        SeatMap { plane, taken, ours }
+    }
+}
+
+impl<'a> std::fmt::Display for SeatMap<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = (self.plane.rows - 1).to_string().len();
+        for row in 0..self.plane.rows {
+            write!(f, "{:>width$} ", row, width = width)?;
+            for col in 0..self.plane.cols {
+                let id = self.plane.seat_id(&Seat::new(row, col));
+                let marker = if Some(id) == self.ours {
+                    'O'
+                } else if self.taken[id as usize] {
+                    '#'
+                } else {
+                    '.'
+                };
+                write!(f, "{}", marker)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -123,7 +254,7 @@ mod tests {
 
         #[test]
         fn find_seat() {
-            let p = Plane::new(128, 8);
+            let p = Plane::new(128, 8).unwrap();
 
             assert_eq!(p.find_seat("FBFBBFFRLR"), Ok(Seat { row: 44, col: 5 }),);
             assert_eq!(p.find_seat("BFFFBBFRRR"), Ok(Seat { row: 70, col: 7 }),);
@@ -131,11 +262,139 @@ mod tests {
             assert_eq!(p.find_seat("BBFFBBFRLL"), Ok(Seat { row: 102, col: 4 }),);
         }
 
+        #[test]
+        fn find_seat_invalid() {
+            let p = Plane::new(128, 8).unwrap();
+
+            assert_eq!(
+                p.find_seat("FBFBBFFRL"),
+                Err(err::ParseError::new("invalid seat format", "FBFBBFFRL")),
+            );
+            assert_eq!(
+                p.find_seat("FBFBBFFRLRR"),
+                Err(err::ParseError::new("invalid seat format", "FBFBBFFRLRR")),
+            );
+            assert_eq!(
+                p.find_seat("FBFBBFRRLR"),
+                Err(err::ParseError::at("invalid seat format", "FBFBBFRRLR", 7)),
+            );
+            assert_eq!(
+                p.find_seat("FBFBBFFRLF"),
+                Err(err::ParseError::at("invalid seat format", "FBFBBFFRLF", 10)),
+            );
+            assert_eq!(
+                p.find_seat("FBFBBFFRLÉ"),
+                Err(err::ParseError::at("invalid seat format", "FBFBBFFRLÉ", 10)),
+            );
+            assert_eq!(p.find_seat(" fbfbbffrlr "), Ok(Seat { row: 44, col: 5 }));
+        }
+
+        #[test]
+        fn find_seat_other_sizes() {
+            let p = Plane::new(4, 2).unwrap();
+            assert_eq!(p.find_seat("BFR"), Ok(Seat { row: 2, col: 1 }));
+            assert_eq!(
+                p.find_seat("FBRL"),
+                Err(err::ParseError::new("invalid seat format", "FBRL"))
+            );
+
+            let p = Plane::new(1024, 16).unwrap();
+            assert_eq!(
+                p.find_seat("BBBBBBBBBBRRRR"),
+                Ok(Seat { row: 1023, col: 15 })
+            );
+            assert_eq!(p.find_seat("FFFFFFFFFBLLLR"), Ok(Seat { row: 1, col: 1 }));
+
+            let p = Plane::new(1, 8).unwrap();
+            assert_eq!(p.find_seat("RLR"), Ok(Seat { row: 0, col: 5 }));
+            assert_eq!(
+                p.find_seat("FRLR"),
+                Err(err::ParseError::new("invalid seat format", "FRLR"))
+            );
+        }
+
+        #[test]
+        fn new_invalid() {
+            assert_eq!(
+                Plane::new(100, 8).err(),
+                Some(err::ParseError::new("rows is not a power of two", "100"))
+            );
+            assert_eq!(
+                Plane::new(128, 0).err(),
+                Some(err::ParseError::new("cols is not a power of two", "0"))
+            );
+            assert_eq!(
+                Plane::new(1 << 20, 1 << 12).err(),
+                Some(err::ParseError::new("too many seats", "1048576x4096"))
+            );
+        }
+
         #[test]
         fn seat_id() {
-            let p = Plane::new(128, 8);
+            let p = Plane::new(128, 8).unwrap();
 
             assert_eq!(p.seat_id(&Seat::new(44, 5)), 357,)
         }
     }
+    mod encode {
+        use super::super::*;
+
+        #[test]
+        fn examples() {
+            let p = Plane::new(128, 8).unwrap();
+
+            assert_eq!(p.encode(&Seat::new(44, 5)), "FBFBBFFRLR");
+            assert_eq!(p.encode(&Seat::new(70, 7)), "BFFFBBFRRR");
+            assert_eq!(p.encode(&Seat::new(14, 7)), "FFFBBBFRRR");
+            assert_eq!(p.encode(&Seat::new(102, 4)), "BBFFBBFRLL");
+        }
+
+        #[test]
+        fn round_trip() {
+            for (rows, cols) in [(128, 8), (4, 2), (1, 8), (16, 1), (1, 1)] {
+                let p = Plane::new(rows, cols).unwrap();
+                for id in 0..rows * cols {
+                    let seat = p.seat(id).unwrap();
+                    assert_eq!(p.seat_id(&seat), id);
+                    assert_eq!(p.find_seat(&p.encode(&seat)), Ok(seat));
+                }
+                assert_eq!(p.seat(rows * cols), None);
+            }
+        }
+
+        #[test]
+        #[should_panic(expected = "is not on the plane")]
+        fn off_plane() {
+            Plane::new(128, 8).unwrap().encode(&Seat::new(128, 0));
+        }
+    }
+
+    mod seatmap {
+        use super::super::*;
+
+        #[test]
+        fn missing() {
+            assert_eq!(missing_ids(&[3, 4, 5, 7, 8]), vec![6]);
+            assert_eq!(missing_ids(&[1, 3, 4, 5]), Vec::<u32>::new());
+            assert_eq!(missing_ids(&[1, 2, 4, 5, 9]), vec![3, 6]);
+            assert_eq!(missing_ids(&[]), Vec::<u32>::new());
+        }
+
+        #[test]
+        fn render() {
+            let p = Plane::new(16, 4).unwrap();
+            let ids = vec![5, 6, 7, 8, 10, 11, 12, 13, 63];
+            let ours = missing_ids(&ids).first().copied();
+            assert_eq!(ours, Some(9));
+
+            let map = SeatMap::new(&p, &ids, ours).to_string();
+            let lines: Vec<&str> = map.lines().collect();
+            assert_eq!(lines.len(), 16);
+            assert_eq!(lines[0], " 0 ....");
+            assert_eq!(lines[1], " 1 .###");
+            assert_eq!(lines[2], " 2 #O##");
+            assert_eq!(lines[3], " 3 ##..");
+            assert_eq!(lines[15], "15 ...#");
+        }
+    }
 }
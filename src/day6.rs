@@ -12,13 +12,12 @@ impl days::Day for Day {
         println!("running day 6");
         let mut file = match File::open("data/06/input.txt") {
             Ok(file) => file,
-            Err(e) => panic!(e),
+            Err(e) => panic!("{:?}", e),
         };
         let mut contents = String::new();
-        match file.read_to_string(&mut contents) {
-            Err(e) => panic!(e),
-            _ => {}
-        };
+        if let Err(e) = file.read_to_string(&mut contents) {
+            panic!("{:?}", e)
+        }
 
         let groups = match parse_groups(&contents) {
             Ok(groups) => groups,
@@ -78,7 +77,7 @@ impl Group {
         }
 
         Ok(Group{
-            surveys: surveys,
+            surveys,
         })
     }
 
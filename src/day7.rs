@@ -1,3 +1,4 @@
+use std::collections::{BTreeMap, HashSet};
 use std::fs::File;
 use std::io::prelude::*;
 
@@ -6,34 +7,86 @@ use crate::err;
 
 #[derive(Debug)]
 pub struct Day {}
-impl days::Day for Day {
-    fn run(&self) {
-        println!("running day 7");
+impl Day {
+    fn load_rulebook(&self) -> Option<Rulebook> {
         let mut file = match File::open("data/07/input.txt") {
             Ok(file) => file,
-            Err(e) => panic!(e),
+            Err(e) => panic!("{:?}", e),
         };
         let mut contents = String::new();
-        match file.read_to_string(&mut contents) {
-            Err(e) => panic!(e),
-            _ => {}
-        };
+        if let Err(e) = file.read_to_string(&mut contents) {
+            panic!("{:?}", e)
+        }
 
-        let rulebook = match Rulebook::parse(&contents) {
-            Ok(rules) => rules,
+        match Rulebook::parse(&contents) {
+            Ok(rules) => Some(rules),
             Err(e) => {
-                println!("could not parse rules: {:?}", e);
-                return;
+                println!("could not parse rules: {}", e);
+                None
             }
+        }
+    }
+}
+
+impl days::Day for Day {
+    fn run(&self) {
+        println!("running day 7");
+        let rulebook = match self.load_rulebook() {
+            Some(rulebook) => rulebook,
+            None => return,
         };
 
         println!("style options: {}", rulebook.find_options(&ColorStyle::new("shiny", "gold")).len());
         println!("color options: {}", rulebook.color_options(&ColorStyle::new("shiny", "gold")).len());
         println!("bags inside: {}", rulebook.bags_inside(&ColorStyle::new("shiny", "gold")));
     }
+
+    // Usage: 7 bom [table|json|csv] [style color]
+    fn run_with_args(&self, args: &[String]) {
+        match args.first().map(|a| a.as_str()) {
+            Some("bom") => {}
+            Some(cmd) => {
+                println!("unknown command: {}", cmd);
+                return;
+            }
+            None => return self.run(),
+        }
+
+        let format = match args.get(1) {
+            Some(raw_format) => match BillFormat::parse(raw_format) {
+                Ok(format) => format,
+                Err(e) => {
+                    println!("could not parse format: {:?}", e);
+                    return;
+                }
+            },
+            None => BillFormat::Table,
+        };
+        let style = match args.get(2..) {
+            Some(raw_style) if !raw_style.is_empty() => {
+                match ColorStyle::parse(&raw_style.join(" ")) {
+                    Ok(style) => style,
+                    Err(e) => {
+                        println!("could not parse style: {:?}", e);
+                        return;
+                    }
+                }
+            }
+            _ => ColorStyle::new("shiny", "gold"),
+        };
+
+        let rulebook = match self.load_rulebook() {
+            Some(rulebook) => rulebook,
+            None => return,
+        };
+        match rulebook.bill_of_materials(&style) {
+            Ok(bill) => print!("{}", bill.render(format)),
+            Err(e) => println!("could not build bill of materials: {}", e),
+        }
+    }
 }
 
-#[derive(PartialEq, PartialOrd, Eq, Ord, Debug)]
+#[derive(PartialEq, PartialOrd, Eq, Ord, Hash, Debug)]
 struct ColorStyle {
     style: String,
     color: String,
@@ -47,20 +100,16 @@ impl ColorStyle {
         }
     }
     fn parse(s: &str) -> Result<ColorStyle, err::ParseError> {
-        let parts: Vec<&str> = s
-            .split_whitespace()
-            .map(|p| p.trim())
-            .filter(|p| !p.is_empty())
-            .collect();
-
-        if parts.len() < 2 {
-            return Err(err::ParseError::new("invalid format", s));
-        }
+        let mut parser = RuleParser::new(s)?;
+        let style = parser.color_style()?;
+        parser.end()?;
+        Ok(style)
+    }
+}
 
-        Ok(ColorStyle {
-            style: parts[0].to_owned(),
-            color: parts[1].to_owned(),
-        })
+impl std::fmt::Display for ColorStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.style, self.color)
     }
 }
 
@@ -70,31 +119,11 @@ struct Contents {
     style: ColorStyle,
 }
 
-impl Contents {
-    fn parse(s: &str) -> Result<Option<Contents>, err::ParseError> {
-        let parts: Vec<&str> = s
-            .split_whitespace()
-            .map(|p| p.trim())
-            .filter(|p| !p.is_empty())
-            .collect();
-        if parts.len() < 2 {
-            return Err(err::ParseError::new("invalid contents format", s));
-        }
-
-        match parts[0..2] {
-            ["no", "other"] => Ok(None),
-            _ => Ok(Some(Contents {
-                amount: match parts[0] {
-                    "no" => 0,
-                    raw_amount => match raw_amount.parse() {
-                        Ok(amount) => amount,
-                        Err(_) => {
-                            return Err(err::ParseError::new("invalid amount for contents", s))
-                        }
-                    },
-                },
-                style: ColorStyle::parse(&parts[1..].join(" "))?,
-            })),
+impl std::fmt::Display for Contents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.amount {
+            1 => write!(f, "1 {} bag", self.style),
+            amount => write!(f, "{} {} bags", amount, self.style),
         }
     }
 }
@@ -107,52 +136,237 @@ struct Rule {
 
 impl Rule {
     fn parse(s: &str) -> Result<Rule, err::ParseError> {
-        let main_parts: Vec<&str> = s
-            .split(" contain ")
-            .map(|p| p.trim())
-            .filter(|p| !p.is_empty())
-            .collect();
-        if main_parts.len() != 2 {
-            return Err(err::ParseError::new("invalid rule format", s));
+        let mut parser = RuleParser::new(s)?;
+        let rule = parser.rule()?;
+        parser.end()?;
+        Ok(rule)
+    }
+
+    fn contains_style(&self, style: &ColorStyle) -> bool {
+        match &self.contents {
+            Some(contents) => !contents
+                .iter()
+                .filter(|c| c.style == *style)
+                .collect::<Vec<&Contents>>()
+                .is_empty(),
+            None => false,
         }
+    }
+}
 
-        let rule_bag = main_parts[0];
-        let rule_constraints: Vec<&str> = main_parts[1]
-            .split(",")
-            .map(|c| c.trim())
-            .filter(|c| !c.is_empty())
-            .collect();
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} bags contain ", self.bag)?;
+        match &self.contents {
+            Some(contents) => {
+                let contents: Vec<String> = contents.iter().map(|c| c.to_string()).collect();
+                write!(f, "{}.", contents.join(", "))
+            }
+            None => write!(f, "no other bags."),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Token<'a> {
+    Word(&'a str),
+    Number(u32),
+    Comma,
+    Period,
+}
+
+#[derive(PartialEq, Debug)]
+struct Lexeme<'a> {
+    token: Token<'a>,
+    col: usize,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Lexeme<'_>>, err::ParseError> {
+    let mut lexemes = Vec::new();
+
+    let mut chars = s.char_indices().enumerate().peekable();
+    while let Some((col_idx, (start, c))) = chars.next() {
+        let col = col_idx + 1;
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            ',' => Token::Comma,
+            '.' => Token::Period,
+            c if c.is_ascii_digit() || c.is_alphabetic() => {
+                let mut end = start + c.len_utf8();
+                while let Some((_, (next_idx, next_c))) = chars.peek() {
+                    if c.is_ascii_digit() && !next_c.is_ascii_digit() {
+                        break;
+                    }
+                    if c.is_alphabetic() && !next_c.is_alphabetic() {
+                        break;
+                    }
+                    end = next_idx + next_c.len_utf8();
+                    chars.next();
+                }
+
+                let raw = &s[start..end];
+                if c.is_ascii_digit() {
+                    match raw.parse::<u32>() {
+                        Ok(amount) => Token::Number(amount),
+                        Err(_) => return Err(err::ParseError::at("invalid amount", s, col)),
+                    }
+                } else {
+                    Token::Word(raw)
+                }
+            }
+            _ => return Err(err::ParseError::at("unexpected character", s, col)),
+        };
+        lexemes.push(Lexeme { token, col });
+    }
+
+    Ok(lexemes)
+}
+
+// Recursive descent parser for the rule grammar:
+//
+//   rule     = style bag "contain" contents "."
+//   contents = "no" "other" bag | entry { "," entry }
+//   entry    = number style bag
+//   style    = word word { word }
+//   bag      = "bag" | "bags"
+struct RuleParser<'a> {
+    src: &'a str,
+    lexemes: Vec<Lexeme<'a>>,
+    idx: usize,
+}
+
+impl<'a> RuleParser<'a> {
+    fn new(src: &'a str) -> Result<RuleParser<'a>, err::ParseError> {
+        Ok(RuleParser {
+            src,
+            lexemes: tokenize(src)?,
+            idx: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<Token<'a>> {
+        self.lexemes.get(self.idx).map(|l| l.token)
+    }
+
+    fn advance(&mut self) {
+        self.idx += 1;
+    }
+
+    fn error(&self, msg: &str) -> err::ParseError {
+        let col = match self.lexemes.get(self.idx) {
+            Some(lexeme) => lexeme.col,
+            None => self.src.chars().count() + 1,
+        };
+        err::ParseError::at(msg, self.src, col)
+    }
+
+    fn is_bag(token: Option<Token>) -> bool {
+        matches!(token, Some(Token::Word("bag")) | Some(Token::Word("bags")))
+    }
+
+    fn expect_word(&mut self, word: &str) -> Result<(), err::ParseError> {
+        match self.peek() {
+            Some(Token::Word(w)) if w == word => {
+                self.advance();
+                Ok(())
+            }
+            _ => Err(self.error(&format!("expected '{}'", word))),
+        }
+    }
+
+    fn expect_bag(&mut self) -> Result<(), err::ParseError> {
+        if !RuleParser::is_bag(self.peek()) {
+            return Err(self.error("expected 'bag' or 'bags'"));
+        }
+        self.advance();
+        Ok(())
+    }
+
+    fn end(&self) -> Result<(), err::ParseError> {
+        match self.peek() {
+            Some(_) => Err(self.error("unexpected trailing input")),
+            None => Ok(()),
+        }
+    }
+
+    fn color_style(&mut self) -> Result<ColorStyle, err::ParseError> {
+        let start = self.idx;
+        let mut words = Vec::new();
+        while let Some(Token::Word(w)) = self.peek() {
+            if RuleParser::is_bag(self.peek()) {
+                break;
+            }
+            words.push(w);
+            self.advance();
+        }
+
+        if words.len() < 2 {
+            self.idx = start;
+            return Err(self.error("expected style and color"));
+        }
+
+        Ok(ColorStyle::new(words[0], &words[1..].join(" ")))
+    }
+
+    fn contents_entry(&mut self) -> Result<Option<Contents>, err::ParseError> {
+        let amount = match self.peek() {
+            Some(Token::Word("no")) => {
+                self.advance();
+                self.expect_word("other")?;
+                self.expect_bag()?;
+                return Ok(None);
+            }
+            Some(Token::Number(amount)) => {
+                self.advance();
+                amount
+            }
+            _ => return Err(self.error("expected amount or 'no other bags'")),
+        };
+
+        let style = self.color_style()?;
+        self.expect_bag()?;
+
+        Ok(Some(Contents { amount, style }))
+    }
+
+    fn rule(&mut self) -> Result<Rule, err::ParseError> {
+        let bag = self.color_style()?;
+        self.expect_bag()?;
+        self.expect_word("contain")?;
 
         let mut contents = Vec::new();
-        for rule_contents in rule_constraints {
-            match Contents::parse(rule_contents) {
-                Ok(c) => match c {
-                    Some(c) => contents.push(c),
-                    None => {}
-                },
-                Err(e) => return Err(e),
-            };
+        match self.contents_entry()? {
+            // "no other bags" can't be mixed with other contents.
+            None => {}
+            Some(entry) => {
+                contents.push(entry);
+                while let Some(Token::Comma) = self.peek() {
+                    self.advance();
+                    let entry_start = self.idx;
+                    match self.contents_entry()? {
+                        Some(entry) => contents.push(entry),
+                        None => {
+                            self.idx = entry_start;
+                            return Err(self.error("unexpected 'no other bags'"));
+                        }
+                    }
+                }
+            }
+        }
+
+        match self.peek() {
+            Some(Token::Period) => self.advance(),
+            _ => return Err(self.error("expected '.'")),
         }
 
         Ok(Rule {
-            bag: ColorStyle::parse(rule_bag)?,
+            bag,
             contents: match contents.len() {
                 0 => None,
                 _ => Some(contents),
             },
         })
     }
-
-    fn contains_style(&self, style: &ColorStyle) -> bool {
-        match &self.contents {
-            Some(contents) => !contents
-                .iter()
-                .filter(|c| c.style == *style)
-                .collect::<Vec<&Contents>>()
-                .is_empty(),
-            None => false,
-        }
-    }
 }
 
 #[derive(PartialEq, Debug)]
@@ -160,29 +374,32 @@ struct Rulebook {
     rules: Vec<Rule>,
 }
 
+impl std::fmt::Display for Rulebook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for rule in &self.rules {
+            writeln!(f, "{}", rule)?;
+        }
+        Ok(())
+    }
+}
+
 impl Rulebook {
     fn parse(s: &str) -> Result<Rulebook, err::ParseError> {
-        let lines: Vec<&str> = s
-            .split("\n")
-            .map(|l| l.trim())
-            .filter(|l| !l.is_empty())
-            .collect();
-
         let mut rules = Vec::new();
-        for line in lines {
-            match Rule::parse(line) {
-                Ok(rule) => rules.push(rule),
-                Err(e) => println!("parse err: {:?}", e),
+        for (idx, line) in s.split('\n').enumerate() {
+            if line.trim().is_empty() {
+                continue;
             }
+            rules.push(Rule::parse(line).map_err(|e| e.on_line(idx + 1))?);
         }
 
-        Ok(Rulebook { rules: rules })
+        Ok(Rulebook { rules })
     }
     
     fn find_rule(&self, style: &ColorStyle) -> Option<&Rule> {
         for rule in &self.rules {
             if rule.bag == *style {
-                return Some(&rule)
+                return Some(rule)
             }
         }
 
@@ -197,7 +414,7 @@ impl Rulebook {
             }
         }
 
-        if found.len() > 0 {
+        if !found.is_empty() {
             let mut next_depth = Vec::new();
             for found_style in &found {
                 let mut next_found = self.find_options(found_style);
@@ -241,6 +458,238 @@ impl Rulebook {
 
         total
     }
+
+    // The first style found inside itself when opening up the given bag,
+    // if there is one. Such a bag would hold infinitely many bags. Styles in
+    // done have already been opened all the way down without finding one, so
+    // bags shared by many others are only checked once.
+    fn find_cycle<'a>(
+        &'a self,
+        style: &'a ColorStyle,
+        path: &mut Vec<&'a ColorStyle>,
+        done: &mut HashSet<&'a ColorStyle>,
+    ) -> Option<&'a ColorStyle> {
+        if path.contains(&style) {
+            return Some(style);
+        }
+        if done.contains(style) {
+            return None;
+        }
+
+        if let Some(rule_contents) = self.find_rule(style).and_then(|r| r.contents.as_ref()) {
+            path.push(style);
+            for contents in rule_contents {
+                if let Some(cycle) = self.find_cycle(&contents.style, path, done) {
+                    return Some(cycle);
+                }
+            }
+            path.pop();
+        }
+        done.insert(style);
+
+        None
+    }
+
+    fn bill_of_materials<'a>(
+        &'a self,
+        style: &'a ColorStyle,
+    ) -> Result<BillOfMaterials<'a>, BillError> {
+        if let Some(cycle) = self.find_cycle(style, &mut Vec::new(), &mut HashSet::new()) {
+            return Err(BillError::Cycle(cycle.to_string()));
+        }
+
+        let mut entries = Vec::new();
+
+        // Walk the bags one nesting level at a time, carrying how many of
+        // each style are at the current level so the amounts multiply down.
+        let mut level: BTreeMap<&ColorStyle, u32> = BTreeMap::new();
+        level.insert(style, 1);
+
+        let mut depth = 0;
+        while !level.is_empty() {
+            depth += 1;
+
+            let mut next_level: BTreeMap<&ColorStyle, u32> = BTreeMap::new();
+            for (level_style, level_amount) in &level {
+                let rule = match self.find_rule(level_style) {
+                    Some(rule) => rule,
+                    None => continue,
+                };
+                if let Some(rule_contents) = &rule.contents {
+                    for contents in rule_contents {
+                        let next_amount = next_level.entry(&contents.style).or_insert(0);
+                        *next_amount = level_amount
+                            .checked_mul(contents.amount)
+                            .and_then(|amount| amount.checked_add(*next_amount))
+                            .ok_or_else(|| err::OverflowError::new(
+                                "amount",
+                                &contents.style.to_string(),
+                                "u32",
+                            ))?;
+                    }
+                }
+            }
+
+            for (next_style, next_amount) in &next_level {
+                entries.push(BillEntry {
+                    depth,
+                    style: next_style,
+                    amount: *next_amount,
+                });
+            }
+            level = next_level;
+        }
+
+        // Make sure the total fits too, so rendering the bill can't overflow.
+        entries
+            .iter()
+            .try_fold(0u32, |total, e| total.checked_add(e.amount))
+            .ok_or_else(|| err::OverflowError::new("total", &style.to_string(), "u32"))?;
+
+        Ok(BillOfMaterials {
+            bag: style,
+            entries,
+        })
+    }
+}
+
+// Why a bill of materials couldn't be made for a bag.
+#[derive(PartialEq, Debug)]
+enum BillError {
+    Overflow(err::OverflowError),
+    // The style of a bag that ends up inside itself.
+    Cycle(String),
+}
+
+impl From<err::OverflowError> for BillError {
+    fn from(e: err::OverflowError) -> BillError {
+        BillError::Overflow(e)
+    }
+}
+
+impl std::fmt::Display for BillError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BillError::Overflow(e) => write!(f, "{}", e),
+            BillError::Cycle(style) => write!(f, "{} bags contain themselves", style),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum BillFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+impl BillFormat {
+    fn parse(s: &str) -> Result<BillFormat, err::ParseError> {
+        match s.trim().to_lowercase().as_str() {
+            "table" => Ok(BillFormat::Table),
+            "json" => Ok(BillFormat::Json),
+            "csv" => Ok(BillFormat::Csv),
+            _ => Err(err::ParseError::new("unknown bill format", s)),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+struct BillEntry<'a> {
+    depth: u32,
+    style: &'a ColorStyle,
+    amount: u32,
+}
+
+#[derive(PartialEq, Debug)]
+struct BillOfMaterials<'a> {
+    bag: &'a ColorStyle,
+    entries: Vec<BillEntry<'a>>,
+}
+
+impl<'a> BillOfMaterials<'a> {
+    fn total(&self) -> u32 {
+        self.entries.iter().map(|e| e.amount).sum()
+    }
+
+    fn depths(&self) -> Vec<u32> {
+        let mut depths: Vec<u32> = self.entries.iter().map(|e| e.depth).collect();
+        depths.dedup();
+        depths
+    }
+
+    fn render(&self, format: BillFormat) -> String {
+        match format {
+            BillFormat::Table => self.render_table(),
+            BillFormat::Json => self.render_json(),
+            BillFormat::Csv => self.render_csv(),
+        }
+    }
+
+    fn render_table(&self) -> String {
+        let style_width = self
+            .entries
+            .iter()
+            .map(|e| e.style.to_string().len())
+            .fold("style".len(), |acc, w| acc.max(w));
+
+        let row = |depth: &str, style: &str, amount: &str| {
+            format!(
+                "{:<5}  {:<width$}  {:>6}\n",
+                depth,
+                style,
+                amount,
+                width = style_width
+            )
+        };
+
+        let mut out = format!("bill of materials for {}\n", self.bag);
+        out += &row("depth", "style", "amount");
+        for entry in &self.entries {
+            out += &row(
+                &entry.depth.to_string(),
+                &entry.style.to_string(),
+                &entry.amount.to_string(),
+            );
+        }
+        out += &row("", "total", &self.total().to_string());
+        out
+    }
+
+    fn render_json(&self) -> String {
+        let depths: Vec<String> = self
+            .depths()
+            .iter()
+            .map(|depth| {
+                let contents: Vec<String> = self
+                    .entries
+                    .iter()
+                    .filter(|e| e.depth == *depth)
+                    .map(|e| format!(r#"{{"style":"{}","amount":{}}}"#, e.style, e.amount))
+                    .collect();
+                format!(
+                    r#"{{"depth":{},"contents":[{}]}}"#,
+                    depth,
+                    contents.join(",")
+                )
+            })
+            .collect();
+
+        format!(
+            r#"{{"bag":"{}","total":{},"depths":[{}]}}"#,
+            self.bag,
+            self.total(),
+            depths.join(",")
+        ) + "\n"
+    }
+
+    fn render_csv(&self) -> String {
+        let mut out = String::from("depth,style,amount\n");
+        for entry in &self.entries {
+            out += &format!("{},{},{}\n", entry.depth, entry.style, entry.amount);
+        }
+        out
+    }
 }
 
 #[cfg(test)]
@@ -279,10 +728,14 @@ mod tests {
     mod contents {
         use super::super::*;
 
+        fn contents(s: &str) -> Result<Option<Vec<Contents>>, err::ParseError> {
+            Rule::parse(&format!("shiny gold bags contain {}.", s)).map(|r| r.contents)
+        }
+
         match_parse! {
-            parse_no_other:       Contents::parse("no other bags"), None,
-            parse_7_light_red:    Contents::parse("7 light red"), Some(Contents{amount: 7, style: ColorStyle::new("light", "red")}),
-            parse_10_muted_black: Contents::parse("10 muted black"), Some(Contents{amount: 10, style: ColorStyle::new("muted", "black")}),
+            parse_no_other:       contents("no other bags"), None,
+            parse_7_light_red:    contents("7 light red bags"), Some(vec![Contents{amount: 7, style: ColorStyle::new("light", "red")}]),
+            parse_10_muted_black: contents("10 muted black bags"), Some(vec![Contents{amount: 10, style: ColorStyle::new("muted", "black")}]),
         }
     }
     mod rule {
@@ -363,6 +816,72 @@ mod tests {
                 false,
             );
         }
+
+        match_parse! {
+            parse_multi_word_color:
+                Rule::parse("light sky blue bags contain 1 dark sea green bag."),
+                Rule{
+                    bag: ColorStyle::new("light", "sky blue"),
+                    contents: Some(vec![
+                        Contents{amount: 1, style: ColorStyle::new("dark", "sea green")},
+                    ]),
+                },
+            parse_loose_plurals:
+                Rule::parse("light red bag contain 1 bright white bags , 2 muted yellow bag ."),
+                Rule{
+                    bag: ColorStyle::new("light", "red"),
+                    contents: Some(vec![
+                        Contents{amount: 1, style: ColorStyle::new("bright", "white")},
+                        Contents{amount: 2, style: ColorStyle::new("muted", "yellow")},
+                    ]),
+                },
+        }
+
+        macro_rules! parse_errors {
+            ($($name:ident: $line:expr, $msg:expr, $col:expr,)*) => {
+                $(
+                    #[test]
+                    fn $name() {
+                        assert_eq!(Rule::parse($line), Err(err::ParseError::at($msg, $line, $col)));
+                    }
+                )*
+            };
+        }
+
+        parse_errors! {
+            parse_err_missing_style:
+                "red bags contain no other bags.", "expected style and color", 1,
+            parse_err_missing_contain:
+                "light red bags contains 1 bright white bag.", "expected 'contain'", 16,
+            parse_err_missing_bag:
+                "light red bags contain 1 bright white.", "expected 'bag' or 'bags'", 38,
+            parse_err_missing_amount:
+                "light red bags contain bright white bags.", "expected amount or 'no other bags'", 24,
+            parse_err_missing_period:
+                "light red bags contain 1 bright white bag", "expected '.'", 42,
+            parse_err_mixed_no_other:
+                "light red bags contain 1 bright white bag, no other bags.", "unexpected 'no other bags'", 44,
+            parse_err_bad_char:
+                "light red bags contain 1 bright white bag $", "unexpected character", 43,
+            parse_err_trailing:
+                "light red bags contain no other bags. extra", "unexpected trailing input", 39,
+        }
+
+        #[test]
+        fn display_canonical() {
+            assert_eq!(
+                Rule::parse("light  red bags contain 1 bright white bags,2 muted yellow bag.")
+                    .unwrap()
+                    .to_string(),
+                "light red bags contain 1 bright white bag, 2 muted yellow bags.",
+            );
+            assert_eq!(
+                Rule::parse("faded blue bag contain no other bag.")
+                    .unwrap()
+                    .to_string(),
+                "faded blue bags contain no other bags.",
+            );
+        }
     }
 
     mod rulebook {
@@ -461,6 +980,25 @@ mod tests {
                 },
         }
 
+        #[test]
+        fn parse_error_line() {
+            let line = "light red bags contain 1 bright white bag";
+            assert_eq!(
+                Rulebook::parse(&format!("\n{}\n", line)),
+                Err(err::ParseError::at("expected '.'", line, 42).on_line(2)),
+            );
+        }
+
+        #[test]
+        fn display_round_trip() {
+            let book = make_options_book();
+            assert_eq!(Rulebook::parse(&book.to_string()), Ok(book));
+            assert_eq!(
+                make_depth_book().to_string().lines().next(),
+                Some("shiny gold bags contain 2 dark red bags."),
+            );
+        }
+
         #[test]
         fn find_options_1() {
             let book = make_options_book();
@@ -540,5 +1078,173 @@ mod tests {
                 126,
             )
         }
+
+        #[test]
+        fn bill_of_materials_depth() {
+            let book = make_depth_book();
+            let bag = ColorStyle::new("dark", "green");
+            let bill = book.bill_of_materials(&bag).unwrap();
+            assert_eq!(
+                bill.entries,
+                vec![
+                    BillEntry {
+                        depth: 1,
+                        style: &ColorStyle::new("dark", "blue"),
+                        amount: 2,
+                    },
+                    BillEntry {
+                        depth: 2,
+                        style: &ColorStyle::new("dark", "violet"),
+                        amount: 4,
+                    },
+                ],
+            );
+            assert_eq!(bill.total(), book.bags_inside(&bag));
+        }
+
+        #[test]
+        fn bill_of_materials_groups_styles() {
+            let book = make_options_book();
+            let bag = ColorStyle::new("shiny", "gold");
+            let bill = book.bill_of_materials(&bag).unwrap();
+            assert_eq!(
+                bill.entries,
+                vec![
+                    BillEntry {
+                        depth: 1,
+                        style: &ColorStyle::new("dark", "olive"),
+                        amount: 1,
+                    },
+                    BillEntry {
+                        depth: 1,
+                        style: &ColorStyle::new("vibrant", "plum"),
+                        amount: 2,
+                    },
+                    BillEntry {
+                        depth: 2,
+                        style: &ColorStyle::new("dotted", "black"),
+                        amount: 16,
+                    },
+                    BillEntry {
+                        depth: 2,
+                        style: &ColorStyle::new("faded", "blue"),
+                        amount: 13,
+                    },
+                ],
+            );
+            assert_eq!(bill.total(), 32);
+        }
+
+        #[test]
+        fn bill_of_materials_empty() {
+            let book = make_depth_book();
+            let bag = ColorStyle::new("dark", "violet");
+            let bill = book.bill_of_materials(&bag).unwrap();
+            assert_eq!(bill.entries, vec![]);
+            assert_eq!(bill.render(BillFormat::Csv), "depth,style,amount\n");
+        }
+
+        #[test]
+        fn bill_of_materials_render() {
+            let book = make_depth_book();
+            let bag = ColorStyle::new("dark", "green");
+            let bill = book.bill_of_materials(&bag).unwrap();
+            assert_eq!(
+                bill.render(BillFormat::Table),
+                "bill of materials for dark green\n\
+                 depth  style        amount\n\
+                 1      dark blue         2\n\
+                 2      dark violet       4\n\
+                 \x20      total             6\n",
+            );
+            assert_eq!(
+                bill.render(BillFormat::Json),
+                r#"{"bag":"dark green","total":6,"depths":[{"depth":1,"contents":[{"style":"dark blue","amount":2}]},{"depth":2,"contents":[{"style":"dark violet","amount":4}]}]}"#.to_owned() + "\n",
+            );
+            assert_eq!(
+                bill.render(BillFormat::Csv),
+                "depth,style,amount\n1,dark blue,2\n2,dark violet,4\n",
+            );
+        }
+
+        #[test]
+        fn bill_of_materials_cycle() {
+            let book = Rulebook::parse(r"
+                shiny gold bags contain 2 dark red bags.
+                dark red bags contain 1 dark orange bag, 1 dark blue bag.
+                dark orange bags contain 2 dark red bags.
+                dark blue bags contain no other bags.
+            ").unwrap();
+            assert_eq!(
+                book.bill_of_materials(&ColorStyle::new("shiny", "gold")),
+                Err(BillError::Cycle(String::from("dark red"))),
+            );
+            assert!(book.bill_of_materials(&ColorStyle::new("dark", "blue")).is_ok());
+        }
+
+        #[test]
+        fn bill_of_materials_shared_contents() {
+            // Every bag holds both bags of the next level down, so there are
+            // 2^40 ways down to the bottom but only 82 bags.
+            let color = |level: u8| {
+                format!("{}{}", (b'a' + level / 26) as char, (b'a' + level % 26) as char)
+            };
+            let mut rules = String::new();
+            for level in 0..40 {
+                for style in &["light", "dark"] {
+                    rules += &format!(
+                        "{} {} bags contain 1 light {} bag, 1 dark {} bag.\n",
+                        style,
+                        color(level),
+                        color(level + 1),
+                        color(level + 1),
+                    );
+                }
+            }
+            rules += &format!("light {} bags contain no other bags.\n", color(40));
+            rules += &format!("dark {} bags contain no other bags.\n", color(40));
+            let book = Rulebook::parse(&rules).unwrap();
+
+            let bag = ColorStyle::new("light", "aa");
+            assert_eq!(book.find_cycle(&bag, &mut Vec::new(), &mut HashSet::new()), None);
+            assert_eq!(
+                book.bill_of_materials(&bag),
+                Err(BillError::Overflow(err::OverflowError::new("amount", "light bh", "u32"))),
+            );
+        }
+
+        #[test]
+        fn bill_of_materials_overflow() {
+            let book = Rulebook::parse(r"
+                shiny gold bags contain 70000 dark red bags.
+                dark red bags contain 70000 dark orange bags.
+                dark orange bags contain no other bags.
+            ").unwrap();
+            assert_eq!(
+                book.bill_of_materials(&ColorStyle::new("shiny", "gold")),
+                Err(BillError::Overflow(err::OverflowError::new("amount", "dark orange", "u32"))),
+            );
+
+            let book = Rulebook::parse(r"
+                shiny gold bags contain 4000000000 dark red bags.
+                dark red bags contain 1 dark orange bag.
+                dark orange bags contain no other bags.
+            ").unwrap();
+            assert_eq!(
+                book.bill_of_materials(&ColorStyle::new("shiny", "gold")),
+                Err(BillError::Overflow(err::OverflowError::new("total", "shiny gold", "u32"))),
+            );
+        }
+
+        #[test]
+        fn bill_format_parse() {
+            assert_eq!(BillFormat::parse("table"), Ok(BillFormat::Table));
+            assert_eq!(BillFormat::parse("JSON"), Ok(BillFormat::Json));
+            assert_eq!(BillFormat::parse("csv"), Ok(BillFormat::Csv));
+            assert_eq!(
+                BillFormat::parse("xml"),
+                Err(err::ParseError::new("unknown bill format", "xml")),
+            );
+        }
     }
 }
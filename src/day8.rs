@@ -11,13 +11,12 @@ impl days::Day for Day {
         println!("running day 8");
         let mut file = match File::open("data/08/input.txt") {
             Ok(file) => file,
-            Err(e) => panic!(e),
+            Err(e) => panic!("{:?}", e),
         };
         let mut contents = String::new();
-        match file.read_to_string(&mut contents) {
-            Err(e) => panic!(e),
-            _ => {}
-        };
+        if let Err(e) = file.read_to_string(&mut contents) {
+            panic!("{:?}", e)
+        }
 
         // Brute force swapping ops until it runs.
         let mut pc: u32 = 0;
@@ -25,7 +24,7 @@ impl days::Day for Day {
             let mut m = Machine::new();
             let mut prog = match Program::parse(&contents) {
                 Ok(prog) => prog,
-                Err(e) => panic!(e),
+                Err(e) => panic!("{:?}", e),
             };
 
             let check_pc = pc;
@@ -158,27 +157,27 @@ impl Op {
         match parts[0] {
             "nop" => match parts[1].parse::<i32>() {
                 Ok(amt) => Ok(Op::Nop(amt)),
-                Err(_) => return Err(err::ParseError::new("invalid nop arg", s)),
+                Err(_) => Err(err::ParseError::new("invalid nop arg", s)),
             },
             "acc" => match parts[1].parse::<i32>() {
                 Ok(amt) => Ok(Op::Acc(amt)),
-                Err(_) => return Err(err::ParseError::new("invalid acc arg", s)),
+                Err(_) => Err(err::ParseError::new("invalid acc arg", s)),
             },
             "jmp" => match parts[1].parse::<i32>() {
                 Ok(amt) => Ok(Op::Jmp(amt)),
-                Err(_) => return Err(err::ParseError::new("invalid jmp arg", s)),
+                Err(_) => Err(err::ParseError::new("invalid jmp arg", s)),
             },
-            _ => return Err(err::ParseError::new("invalid op", s)),
+            _ => Err(err::ParseError::new("invalid op", s)),
         }
     }
 }
 
 impl From<&Op> for Op {
     fn from(op: &Op) -> Self {
-        match op {
-            &Op::Acc(v) => Op::Acc(v),
-            &Op::Jmp(v) => Op::Jmp(v),
-            &Op::Nop(v) => Op::Nop(v),
+        match *op {
+            Op::Acc(v) => Op::Acc(v),
+            Op::Jmp(v) => Op::Jmp(v),
+            Op::Nop(v) => Op::Nop(v),
         }
     }
 }
@@ -200,21 +199,17 @@ impl Program {
             ops.push(Op::parse(line)?)
         }
 
-        Ok(Program { ops: ops })
+        Ok(Program { ops })
     }
 
     fn get(&self, idx: u32) -> Option<Op> {
-        match self.ops.get(idx as usize) {
-            Some(op) => Some(op.into()),
-            None => None,
-        }
+        self.ops.get(idx as usize).map(|op| op.into())
     }
 
     fn swap_op(&mut self, idx: u32, op: Op) {
         let old_op = self.ops.get(idx as usize);
-        match old_op {
-            Some(_) => self.ops[idx as usize] = op,
-            None => return,
+        if old_op.is_some() {
+            self.ops[idx as usize] = op
         }
     }
 }
@@ -1,3 +1,9 @@
 pub trait Day: std::fmt::Debug {
     fn run(&self);
+
+    // Days that take extra command line arguments can override this, the
+    // rest just ignore them.
+    fn run_with_args(&self, _args: &[String]) {
+        self.run()
+    }
 }
@@ -2,6 +2,14 @@
 pub struct ParseError {
     msg: String,
     data: String,
+    pos: Option<Position>,
+}
+
+// 1-based line and column of where a parse error happened.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub col: usize,
 }
 
 impl ParseError {
@@ -9,6 +17,38 @@ impl ParseError {
         ParseError {
             msg: msg.to_owned(),
             data: data.to_owned(),
+            pos: None,
+        }
+    }
+
+    pub fn at(msg: &str, data: &str, col: usize) -> ParseError {
+        ParseError {
+            msg: msg.to_owned(),
+            data: data.to_owned(),
+            pos: Some(Position { line: 1, col }),
+        }
+    }
+
+    pub fn msg(&self) -> &str {
+        &self.msg
+    }
+
+    pub fn on_line(self, line: usize) -> ParseError {
+        ParseError {
+            pos: match self.pos {
+                Some(pos) => Some(Position { line, col: pos.col }),
+                None => Some(Position { line, col: 1 }),
+            },
+            ..self
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.pos {
+            Some(pos) => write!(f, "{}:{}: {}: {}", pos.line, pos.col, self.msg, self.data),
+            None => write!(f, "{}: {}", self.msg, self.data),
         }
     }
 }
@@ -23,7 +63,31 @@ impl ExecError {
     pub fn new(msg: &str, pc: u32) -> ExecError {
         ExecError{
             msg: msg.to_owned(),
-            pc: pc,
+            pc,
         }
     }
-}
\ No newline at end of file
+}
+
+// An arithmetic result that doesn't fit in the type it was computed in.
+#[derive(PartialEq, Debug)]
+pub struct OverflowError {
+    op: String,
+    data: String,
+    ty: String,
+}
+
+impl OverflowError {
+    pub fn new(op: &str, data: &str, ty: &str) -> OverflowError {
+        OverflowError {
+            op: op.to_owned(),
+            data: data.to_owned(),
+            ty: ty.to_owned(),
+        }
+    }
+}
+
+impl std::fmt::Display for OverflowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} of {} overflows {}", self.op, self.data, self.ty)
+    }
+}
@@ -0,0 +1,337 @@
+use crate::err;
+
+// The grid is shared between days, and not every day needs every part of
+// it yet, so the parts no day uses so far are allowed to go unused.
+#[allow(dead_code)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Connectivity {
+    Four,
+    Eight,
+}
+
+#[allow(dead_code)]
+impl Connectivity {
+    fn offsets(&self) -> &'static [(i64, i64)] {
+        match self {
+            Connectivity::Four => &[(0, -1), (1, 0), (0, 1), (-1, 0)],
+            Connectivity::Eight => &[
+                (0, -1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+                (0, 1),
+                (-1, 1),
+                (-1, 0),
+                (-1, -1),
+            ],
+        }
+    }
+}
+
+// A rectangular grid of cells stored row by row, with (0, 0) at the top left.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Result<Grid<T>, err::ParseError> {
+        let width = match rows.first() {
+            Some(row) => row.len(),
+            None => 0,
+        };
+        let height = rows.len();
+
+        let mut cells = Vec::with_capacity(width * height);
+        for (y, row) in rows.into_iter().enumerate() {
+            if row.len() != width {
+                return Err(err::ParseError::new(
+                    "uneven row length",
+                    &format!("row {}", y),
+                ));
+            }
+            cells.extend(row);
+        }
+
+        Ok(Grid {
+            width,
+            height,
+            cells,
+        })
+    }
+
+    // Parses one row per non-blank line, with surrounding whitespace trimmed,
+    // using parse_cell for each character.
+    pub fn parse<F>(s: &str, parse_cell: F) -> Result<Grid<T>, err::ParseError>
+    where
+        F: Fn(char) -> Result<T, err::ParseError>,
+    {
+        let mut rows = Vec::new();
+        for line in s.split('\n').map(|l| l.trim()).filter(|l| !l.is_empty()) {
+            let mut row = Vec::new();
+            for c in line.chars() {
+                row.push(parse_cell(c)?);
+            }
+            rows.push(row);
+        }
+
+        Grid::from_rows(rows)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    #[allow(dead_code)]
+    pub fn in_bounds(&self, x: i64, y: i64) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.cells.get(y * self.width + x)
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.cells.get_mut(y * self.width + x)
+    }
+
+    // Wraps both coordinates around the grid so any x and y land on a cell.
+    #[allow(dead_code)]
+    pub fn get_wrapped(&self, x: i64, y: i64) -> Option<&T> {
+        if self.width == 0 || self.height == 0 {
+            return None;
+        }
+        self.get(
+            x.rem_euclid(self.width as i64) as usize,
+            y.rem_euclid(self.height as i64) as usize,
+        )
+    }
+
+    #[allow(dead_code)]
+    pub fn neighbors(&self, x: usize, y: usize, conn: Connectivity) -> Vec<(usize, usize)> {
+        conn.offsets()
+            .iter()
+            .map(|(dx, dy)| (x as i64 + dx, y as i64 + dy))
+            .filter(|(nx, ny)| self.in_bounds(*nx, *ny))
+            .map(|(nx, ny)| (nx as usize, ny as usize))
+            .collect()
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // chunks panics on a zero size, but an empty grid has no cells anyway.
+        self.cells.chunks(self.width.max(1))
+    }
+
+    #[allow(dead_code)]
+    pub fn row(&self, y: usize) -> Option<&[T]> {
+        if y >= self.height {
+            return None;
+        }
+        Some(&self.cells[y * self.width..(y + 1) * self.width])
+    }
+
+    #[allow(dead_code)]
+    pub fn col(&self, x: usize) -> impl Iterator<Item = &T> {
+        let width = self.width;
+        self.cells
+            .iter()
+            .skip(x)
+            .step_by(width.max(1))
+            .take(if x < width { self.height } else { 0 })
+    }
+
+    #[allow(dead_code)]
+    pub fn cols(&self) -> impl Iterator<Item = Vec<&T>> {
+        (0..self.width).map(move |x| self.col(x).collect())
+    }
+
+    // Every cell along with its coordinates, row by row.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, &T)> {
+        let width = self.width.max(1);
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(idx, cell)| (idx % width, idx / width, cell))
+    }
+
+    #[allow(dead_code)]
+    pub fn map<U, F>(&self, f: F) -> Grid<U>
+    where
+        F: Fn(&T) -> U,
+    {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl<T: Clone> Grid<T> {
+    fn build<F>(width: usize, height: usize, source: F) -> Grid<T>
+    where
+        F: Fn(usize, usize) -> T,
+    {
+        let mut cells = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                cells.push(source(x, y));
+            }
+        }
+        Grid {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    fn at(&self, x: usize, y: usize) -> T {
+        self.cells[y * self.width + x].clone()
+    }
+
+    pub fn rotate_cw(&self) -> Grid<T> {
+        Grid::build(self.height, self.width, |x, y| {
+            self.at(y, self.height - 1 - x)
+        })
+    }
+
+    pub fn rotate_ccw(&self) -> Grid<T> {
+        Grid::build(self.height, self.width, |x, y| {
+            self.at(self.width - 1 - y, x)
+        })
+    }
+
+    pub fn flip_horizontal(&self) -> Grid<T> {
+        Grid::build(self.width, self.height, |x, y| {
+            self.at(self.width - 1 - x, y)
+        })
+    }
+
+    pub fn flip_vertical(&self) -> Grid<T> {
+        Grid::build(self.width, self.height, |x, y| {
+            self.at(x, self.height - 1 - y)
+        })
+    }
+}
+
+impl<T: std::fmt::Display> std::fmt::Display for Grid<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.rows() {
+            for cell in row {
+                write!(f, "{}", cell)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_grid() -> Grid<char> {
+        Grid::parse(
+            r"
+            abc
+            def
+            ",
+            Ok,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let g = make_grid();
+        assert_eq!(g.width(), 3);
+        assert_eq!(g.height(), 2);
+        assert_eq!(g.get(0, 0), Some(&'a'));
+        assert_eq!(g.get(2, 1), Some(&'f'));
+        assert_eq!(g.get(3, 0), None);
+        assert_eq!(g.get(0, 2), None);
+    }
+
+    #[test]
+    fn parse_uneven() {
+        assert_eq!(
+            Grid::parse("abc\nde", Ok),
+            Err(err::ParseError::new("uneven row length", "row 1")),
+        );
+    }
+
+    #[test]
+    fn parse_cell_error() {
+        assert_eq!(
+            Grid::parse("ab", |c| match c {
+                'a' => Ok(1),
+                _ => Err(err::ParseError::new("bad cell", &c.to_string())),
+            }),
+            Err(err::ParseError::new("bad cell", "b")),
+        );
+    }
+
+    #[test]
+    fn get_wrapped() {
+        let g = make_grid();
+        assert_eq!(g.get_wrapped(3, 0), Some(&'a'));
+        assert_eq!(g.get_wrapped(-1, 0), Some(&'c'));
+        assert_eq!(g.get_wrapped(-4, -1), Some(&'f'));
+        assert_eq!(g.get_wrapped(301, 7), Some(&'e'));
+    }
+
+    #[test]
+    fn neighbors() {
+        let g = make_grid();
+        assert_eq!(g.neighbors(0, 0, Connectivity::Four), vec![(1, 0), (0, 1)],);
+        assert_eq!(
+            g.neighbors(1, 0, Connectivity::Eight),
+            vec![(2, 0), (2, 1), (1, 1), (0, 1), (0, 0)],
+        );
+    }
+
+    #[test]
+    fn rows_and_cols() {
+        let g = make_grid();
+        assert_eq!(
+            g.rows().collect::<Vec<&[char]>>(),
+            vec![&['a', 'b', 'c'][..], &['d', 'e', 'f'][..]],
+        );
+        assert_eq!(g.col(1).collect::<Vec<&char>>(), vec![&'b', &'e']);
+        assert_eq!(g.col(3).count(), 0);
+        assert_eq!(g.cols().count(), 3);
+    }
+
+    #[test]
+    fn rotate_and_flip() {
+        let g = make_grid();
+        assert_eq!(g.rotate_cw().to_string(), "da\neb\nfc\n");
+        assert_eq!(g.rotate_ccw().to_string(), "cf\nbe\nad\n");
+        assert_eq!(g.rotate_cw().rotate_ccw(), g);
+        assert_eq!(g.flip_horizontal().to_string(), "cba\nfed\n");
+        assert_eq!(g.flip_vertical().to_string(), "def\nabc\n");
+    }
+
+    #[test]
+    fn empty() {
+        let g: Grid<char> = Grid::parse("", Ok).unwrap();
+        assert_eq!(g.width(), 0);
+        assert_eq!(g.height(), 0);
+        assert_eq!(g.get_wrapped(1, 1), None);
+        assert_eq!(g.rows().count(), 0);
+        assert_eq!(g.to_string(), "");
+    }
+}
@@ -1,7 +1,9 @@
 use std::env;
 
+mod config;
 mod days;
 mod err;
+mod grid;
 
 mod day1;
 mod day2;
@@ -24,12 +26,13 @@ fn main() {
         Box::new(day8::Day {}),
     ];
 
-    match env::args().collect::<Vec<String>>().get(1) {
+    let args = env::args().collect::<Vec<String>>();
+    match args.get(1) {
         Some(arg) => match arg.parse::<usize>() {
             Ok(day) => {
                 let idx = day - 1;
                 match aoc_days.get(idx) {
-                    Some(runner) => runner.run(),
+                    Some(runner) => runner.run_with_args(&args[2..]),
                     None => println!("could not find runner for day {}", day),
                 }
             }